@@ -1,30 +1,277 @@
 
 use serde::{Serialize, Deserialize};
-use crate::auth_route::tokens::{MinecraftProfile, MinecraftToken, OAuthToken};
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::tokens::{MinecraftProfile, MinecraftToken, OAuthToken, XboxClaims};
+use crate::auth_route::auth_process::CLIENT_ID;
+use crate::auth_route::utils;
+use crate::auth_route::yggdrasil::YggdrasilServer;
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct AccountStorage {
     pub accounts: Vec<Account>,
+    #[serde(default)]
+    pub identities: Vec<MicrosoftIdentity>,
     pub elected_account: Option<String>,
 }
 
+impl AccountStorage {
+
+    pub fn find(&self, id: &str) -> Result<&Account, AuthError> {
+        self.accounts
+            .iter()
+            .find(|it| it.profile.id == id)
+            .ok_or_else(|| AuthError::UnknownAccount(id.to_string()))
+    }
+
+    pub fn identity(&self, id: &str) -> Option<&MicrosoftIdentity> {
+        self.identities.iter().find(|it| it.id == id)
+    }
+
+    /// Copy of the account and of the identity owning it.
+    pub fn session(&self, id: &str) -> Result<(Account, Option<MicrosoftIdentity>), AuthError> {
+        let account = self.find(id)?.clone();
+        let identity = account
+            .identity
+            .as_ref()
+            .and_then(|identity| self.identity(identity))
+            .cloned();
+
+        Ok((account, identity))
+    }
+
+    /// Stores back a session obtained through `session`.
+    /// Returns false if the account has been removed in the meantime.
+    pub fn store_session(&mut self, account: Account, identity: Option<MicrosoftIdentity>) -> bool {
+        if !self.replace(account) {
+            return false;
+        }
+
+        if let Some(identity) = identity {
+            self.replace_identity(identity);
+        }
+
+        true
+    }
+
+    /// Stores the account, replacing the one with the same profile id if any
+    /// so it keeps its position in the list.
+    pub fn upsert(&mut self, account: Account) {
+        if !self.replace(account.clone()) {
+            self.accounts.push(account);
+        }
+    }
+
+    pub fn upsert_identity(&mut self, identity: MicrosoftIdentity) {
+        if !self.replace_identity(identity.clone()) {
+            self.identities.push(identity);
+        }
+    }
+
+    /// Replaces the stored identity with the same id.
+    /// Returns false if the identity is no longer stored.
+    pub fn replace_identity(&mut self, identity: MicrosoftIdentity) -> bool {
+        match self.identities.iter().position(|it| it.id == identity.id) {
+            Some(position) => {
+                self.identities[position] = identity;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the account, un-electing it if needed, and returns it.
+    /// The identity owning it is dropped as well once it has no profiles left.
+    pub fn remove(&mut self, id: &str) -> Result<Account, AuthError> {
+        let position = self.accounts
+            .iter()
+            .position(|it| it.profile.id == id)
+            .ok_or_else(|| AuthError::UnknownAccount(id.to_string()))?;
+
+        if self.elected_account.as_deref() == Some(id) {
+            self.elected_account = None;
+        }
+
+        let account = self.accounts.remove(position);
+
+        if let Some(identity) = &account.identity {
+            if !self.accounts.iter().any(|it| it.identity.as_ref() == Some(identity)) {
+                self.identities.retain(|it| &it.id != identity);
+            }
+        }
+
+        Ok(account)
+    }
+
+    pub fn elect(&mut self, id: &str) -> Result<(), AuthError> {
+        self.find(id)?;
+        self.elected_account = Some(id.to_string());
+
+        Ok(())
+    }
+
+    /// Replaces the stored account sharing the same profile id.
+    /// Returns false if the account is no longer stored.
+    pub fn replace(&mut self, account: Account) -> bool {
+        match self.accounts.iter().position(|it| it.profile.id == account.profile.id) {
+            Some(position) => {
+                self.accounts[position] = account;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stores the tokens of `refreshed`, obtained from the copy `previous`, unless the
+    /// account has been removed or signed in again in the meantime.
+    /// Returns whether the stored account has been updated.
+    pub fn merge_refreshed(&mut self, previous: &Account, refreshed: Account) -> bool {
+        match self.accounts.iter_mut().find(|it| it.profile.id == refreshed.profile.id) {
+            Some(stored) if stored.mc.access_token == previous.mc.access_token => {
+                stored.profile = refreshed.profile;
+                stored.mc = refreshed.mc;
+                stored.mc_exp_time = refreshed.mc_exp_time;
+                stored.needs_login = refreshed.needs_login;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Like `merge_refreshed`, for the tokens of an identity.
+    pub fn merge_refreshed_identity(
+        &mut self,
+        previous: &MicrosoftIdentity,
+        refreshed: MicrosoftIdentity,
+    ) -> bool {
+        match self.identities.iter_mut().find(|it| it.id == refreshed.id) {
+            Some(stored) if stored.auth.refresh_token == previous.auth.refresh_token => {
+                *stored = refreshed;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves the OAuth tokens stored by older versions inside each account
+    /// into their own identity. Returns whether anything was migrated.
+    pub fn migrate_legacy(&mut self) -> bool {
+        let mut migrated = false;
+
+        for account in self.accounts.iter_mut() {
+            if let Some(auth) = account.legacy_auth.take() {
+                let identity = MicrosoftIdentity::new(auth, account.legacy_auth_exp_time);
+                account.identity = Some(identity.id.clone());
+                self.identities.push(identity);
+                migrated = true;
+            }
+        }
+
+        migrated
+    }
+
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountKind {
+    Microsoft,
+    /// Non-premium account, only usable on LAN worlds and servers in offline mode.
+    Offline,
+    /// Account of a custom authentication server, launched through authlib-injector.
+    Yggdrasil,
+}
+
+impl Default for AccountKind {
+    fn default() -> Self {
+        AccountKind::Microsoft
+    }
+}
+
+/// A Microsoft sign-in, which can own several Minecraft profiles.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MicrosoftIdentity {
+    pub id: String,
+    pub xuid: Option<String>,
+    pub gamertag: Option<String>,
+    /// Client the OAuth tokens were issued to.
+    pub client_id: String,
+    #[serde(with = "crate::files::secrets::sealed")]
+    pub auth: OAuthToken,
+    pub auth_exp_time: u64,
+}
+
+impl MicrosoftIdentity {
+
+    pub fn new(auth: OAuthToken, auth_exp_time: u64) -> Self {
+        Self {
+            id: utils::next_state(),
+            xuid: None,
+            gamertag: None,
+            client_id: CLIENT_ID.to_string(),
+            auth,
+            auth_exp_time,
+        }
+    }
+
+    /// Keeps the known xuid and gamertag when the claims don't include them.
+    pub fn apply_claims(&mut self, claims: XboxClaims) {
+        if claims.xuid.is_some() {
+            self.xuid = claims.xuid;
+        }
+
+        if claims.gamertag.is_some() {
+            self.gamertag = claims.gamertag;
+        }
+    }
+
+    pub fn is_auth_expired(&self) -> bool {
+        self.auth_exp_time <= std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+}
+
+/// A Minecraft profile the user can play with.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Account {
+    #[serde(default)]
+    pub kind: AccountKind,
     pub profile: MinecraftProfile,
+    /// Id of the `MicrosoftIdentity` owning this profile, none for offline accounts.
+    #[serde(default)]
+    pub identity: Option<String>,
+    #[serde(with = "crate::files::secrets::sealed")]
     pub mc: MinecraftToken,
-    pub auth: OAuthToken,
     pub mc_exp_time: u64,
-    pub auth_exp_time: u64,
+    /// Server issuing the tokens of Yggdrasil accounts.
+    #[serde(default)]
+    pub server: Option<YggdrasilServer>,
+    /// Set when the tokens could not be refreshed and the user has to sign in again.
+    #[serde(default)]
+    pub needs_login: bool,
+    /// OAuth token stored by versions without identities, see `AccountStorage::migrate_legacy`.
+    #[serde(rename = "auth", default, skip_serializing, with = "crate::files::secrets::sealed")]
+    legacy_auth: Option<OAuthToken>,
+    #[serde(rename = "auth_exp_time", default, skip_serializing)]
+    legacy_auth_exp_time: u64,
 }
 
 impl Account {
-    
+
+    pub fn new(kind: AccountKind, profile: MinecraftProfile, identity: Option<String>, mc: MinecraftToken, mc_exp_time: u64) -> Self {
+        Self {
+            kind,
+            profile,
+            identity,
+            mc,
+            mc_exp_time,
+            server: None,
+            needs_login: false,
+            legacy_auth: None,
+            legacy_auth_exp_time: 0,
+        }
+    }
+
     pub fn is_mc_expired(&self) -> bool {
         self.mc_exp_time <= std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
     }
-    
-    pub fn is_auth_expired(&self) -> bool {
-        self.auth_exp_time <= std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
-    }
-    
-}
\ No newline at end of file
+
+}
@@ -1,36 +1,28 @@
 use reqwest::Request;
 use serde::de::DeserializeOwned;
-use serde_json::json;
 
 use super::{
+    code_processor::CodeProcessor,
     errors::AuthError,
-    tokens::{MinecraftProfile, MinecraftToken, OAuthToken, XBLToken, XSTSToken},
-    utils,
+    tokens::{MinecraftProfile, MinecraftToken, OAuthToken, XboxClaims},
 };
 
-use futures::FutureExt;
-
 const BASE_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize";
-const CLIENT_ID: &str = "091170c6-c12e-4075-b7d0-05c916708c31";
+pub(super) const CLIENT_ID: &str = "091170c6-c12e-4075-b7d0-05c916708c31";
 
 const OAUTH_TOKEN_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/token";
-const XBL_TOKEN_URL: &str = "https://user.auth.xboxlive.com/user/authenticate";
-const XSTS_TOKEN_URL: &str = "https://xsts.auth.xboxlive.com/xsts/authorize";
-const MINECRAFT_TOKEN_URL: &str =
-    "https://api.minecraftservices.com/authentication/login_with_xbox";
-const MINECRAFT_PROFILE_URL: &str = "https://api.minecraftservices.com/minecraft/profile";
 
 const SCOPE: &str = "XboxLive.signin offline_access";
 
-/// Generates a URL to authenticate with Microsoft and returns the URL and the state.
+/// Generates a URL to authenticate with Microsoft, the provider redirects back with `state`.
 ///
 /// # Example
 ///
 /// ```
-/// let (uri, state) = create_authentication_url("http://localhost:3000");
+/// let state = utils::next_state();
+/// let uri = create_authentication_url("http://localhost:3000/", &state);
 /// ```
-pub fn create_authentication_url(redirect_uri: &str) -> (String, String) {
-    let state = utils::next_state();
+pub fn create_authentication_url(redirect_uri: &str, state: &str) -> String {
     let params = [
         ("response_type", "code"),
         ("client_id", CLIENT_ID),
@@ -38,7 +30,7 @@ pub fn create_authentication_url(redirect_uri: &str) -> (String, String) {
         ("scope", SCOPE),
         ("response_mode", "query"),
         ("prompt", "select_account"),
-        ("state", &state),
+        ("state", state),
     ];
 
     let query = params
@@ -58,7 +50,7 @@ pub fn create_authentication_url(redirect_uri: &str) -> (String, String) {
             acc
         });
 
-    (format!("{}{}", BASE_URL, query), state)
+    format!("{}{}", BASE_URL, query)
 }
 
 async fn extract_response<T, O>(
@@ -113,80 +105,11 @@ async fn process_code(redirect_uri: &str, code: &str) -> Result<OAuthToken, Auth
     extract_response(client, request, |err| AuthError::OAuthError(err)).await
 }
 
-async fn process_oauth_token(token: &OAuthToken) -> Result<XBLToken, AuthError> {
-    let client = reqwest::Client::new();
-
-    let token = format!("d={}", token.access_token);
-    let request = client
-        .post(XBL_TOKEN_URL)
-        .json(&json!({
-            "Properties": {
-                "AuthMethod": "RPS",
-                "SiteName": "user.auth.xboxlive.com",
-                "RpsTicket": token
-            },
-            "RelyingParty": "http://auth.xboxlive.com",
-            "TokenType": "JWT"
-        }))
-        .build()
-        .expect("Failed to build xbl token request");
-
-    extract_response(client, request, |err| AuthError::XBLError(err)).await
-}
-
-async fn process_xbl_token(token: XBLToken) -> Result<XSTSToken, AuthError> {
-    let token = token.token;
-
-    let client = reqwest::Client::new();
-    let request = client
-        .post(XSTS_TOKEN_URL)
-        .json(&json!({
-            "Properties": {
-                "SandboxId": "RETAIL",
-                "UserTokens": [token]
-            },
-            "RelyingParty": "rp://api.minecraftservices.com/",
-            "TokenType": "JWT"
-        }))
-        .build()
-        .expect("Failed to build xsts token request");
-
-    extract_response(client, request, |err| AuthError::XSTSError(err)).await
-}
-
-async fn process_xsts_token(token: XSTSToken) -> Result<MinecraftToken, AuthError> {
-    let client = reqwest::Client::new();
-    if let Some((uhs, token)) = token.extract_tokens() {
-        let token = format!("XBL3.0 x={};{}", uhs, token);
-        let request = client
-            .post(MINECRAFT_TOKEN_URL)
-            .json(&json!({ "identityToken": token }))
-            .build()
-            .expect("Failed to build minecraft token request");
-
-        extract_response(client, request, |err| AuthError::MinecraftTokenError(err)).await
-    } else {
-        Err(AuthError::MinecraftTokenError(
-            "Failed to extract tokens from xsts token".to_string(),
-        ))
-    }
-}
-
-async fn process_minecraft_token(token: &MinecraftToken) -> Result<MinecraftProfile, AuthError> {
-    let client = reqwest::Client::new();
-    let request = client
-        .get(MINECRAFT_PROFILE_URL)
-        .bearer_auth(&token.access_token)
-        .build()
-        .expect("Failed to build minecraft profile request");
-
-    extract_response(client, request, |_| AuthError::MissingMinecraftProfile).await
-}
-
 pub struct AuthParameters {
     pub oauth_token: OAuthToken,
     pub minecraft_token: MinecraftToken,
     pub minecraft_profile: MinecraftProfile,
+    pub xbox_claims: XboxClaims,
 }
 
 pub async fn process_authentication_code(
@@ -194,26 +117,14 @@ pub async fn process_authentication_code(
     code: &str,
 ) -> Result<AuthParameters, AuthError> {
     let oauth_token = process_code(redirect_uri, code).await?;
-    let minecraft_token = process_oauth_token(&oauth_token)
-        .then(|res| async move {
-            match res.map(|token| process_xbl_token(token)) {
-                Ok(a) => a.await,
-                Err(err) => Err(err),
-            }
-        })
-        .then(|res| async move {
-            match res.map(|token| process_xsts_token(token)) {
-                Ok(a) => a.await,
-                Err(err) => Err(err),
-            }
-        })
+    let (minecraft_token, minecraft_profile, xbox_claims) = CodeProcessor::new()
+        .auth_minecraft_token(&oauth_token)
         .await?;
 
-    let minecraft_profile = process_minecraft_token(&minecraft_token).await?;
-
     Ok(AuthParameters {
         oauth_token,
         minecraft_token,
         minecraft_profile,
+        xbox_claims,
     })
 }
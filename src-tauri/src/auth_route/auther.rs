@@ -1,9 +1,12 @@
 use crate::auth_route::code_processor;
 use crate::auth_route::code_processor::CodeProcessor;
 use crate::auth_route::errors::AuthError;
-use crate::auth_route::tokens::{MinecraftProfile, MinecraftToken, OAuthToken};
+use crate::auth_route::provider::AccountProvider;
+use crate::auth_route::tokens::{MinecraftProfile, MinecraftToken, OAuthToken, XboxClaims};
+use crate::auth_route::accounts::{AccountKind, MicrosoftIdentity};
 use crate::{Account, Storage};
 use serde::Serialize;
+use std::time::Duration;
 use tauri::{AppHandle, Manager, Window};
 
 use super::auth_process::AuthParameters;
@@ -33,7 +36,14 @@ async fn start_process(handle: &AppHandle, window: &Window) -> Result<(), AuthEr
         )
         .ok();
 
-    let (code, redirect_uri) = code_listener::create(&handle).await?;
+    let (timeout, language) = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let storage = storage.read().unwrap();
+        let launcher = &storage.settings.launcher.contents;
+        (Duration::from_secs(launcher.login_timeout), launcher.language)
+    };
+
+    let login = code_listener::create(&handle, timeout, language).await?;
 
     window
         .emit(
@@ -49,7 +59,11 @@ async fn start_process(handle: &AppHandle, window: &Window) -> Result<(), AuthEr
         minecraft_profile,
         minecraft_token,
         oauth_token,
-    } = auth_process::process_authentication_code(&redirect_uri, &code).await?;
+        xbox_claims,
+    } = auth_process::process_authentication_code(&login.redirect_uri, &login.code).await?;
+
+    // The login window only tells the user it worked once we hold the tokens.
+    login.succeed();
 
     window
         .emit(
@@ -66,16 +80,6 @@ async fn start_process(handle: &AppHandle, window: &Window) -> Result<(), AuthEr
 
     let store = &mut storage.settings.accounts.contents;
 
-    // If an account with the same id already exists we remove it.
-    //   In the big screen we are replacing it for a more updated token.
-    if let Some(account_position) = store
-        .accounts
-        .iter()
-        .position(|it| it.profile.id == minecraft_profile.id)
-    {
-        store.accounts.remove(account_position);
-    }
-
     let id = minecraft_profile.id.clone();
 
     let start = std::time::SystemTime::now();
@@ -87,13 +91,53 @@ async fn start_process(handle: &AppHandle, window: &Window) -> Result<(), AuthEr
     let mc_exp_time = now + minecraft_token.expires_in;
     let auth_exp_time = now + oauth_token.expires_in;
 
-    store.accounts.push(Account {
-        auth: oauth_token,
-        profile: minecraft_profile,
-        mc: minecraft_token,
+    // Signing in again reuses the identity of the same Xbox user,
+    //   or the one already owning this profile.
+    let owner = store
+        .find(&minecraft_profile.id)
+        .ok()
+        .and_then(|account| account.identity.clone());
+
+    let existing = store
+        .identities
+        .iter()
+        .find(|it| {
+            (xbox_claims.xuid.is_some() && it.xuid == xbox_claims.xuid)
+                || Some(&it.id) == owner.as_ref()
+        })
+        .cloned();
+
+    let mut identity = match existing {
+        Some(mut identity) => {
+            identity.auth = oauth_token;
+            identity.auth_exp_time = auth_exp_time;
+            identity
+        }
+        None => MicrosoftIdentity::new(oauth_token, auth_exp_time),
+    };
+    identity.apply_claims(xbox_claims);
+
+    // The other profiles owned by the identity share its Minecraft token.
+    for account in store
+        .accounts
+        .iter_mut()
+        .filter(|it| it.identity.as_ref() == Some(&identity.id))
+    {
+        account.mc = minecraft_token.clone();
+        account.mc_exp_time = mc_exp_time;
+        account.needs_login = false;
+    }
+
+    // If an account with the same id already exists it is replaced in place,
+    //   in the big screen we are replacing it for a more updated token.
+    store.upsert(Account::new(
+        AccountKind::Microsoft,
+        minecraft_profile,
+        Some(identity.id.clone()),
+        minecraft_token,
         mc_exp_time,
-        auth_exp_time,
-    });
+    ));
+    store.upsert_identity(identity);
 
     store.elected_account = Some(id);
 
@@ -123,31 +167,84 @@ pub async fn refresh_oauth(auth_token: &OAuthToken) -> Result<OAuthToken, AuthEr
 
 pub async fn auth_minecraft_token(
     auth_token: &OAuthToken,
-) -> Result<(MinecraftToken, MinecraftProfile), AuthError> {
+) -> Result<(MinecraftToken, MinecraftProfile, XboxClaims), AuthError> {
     CodeProcessor::new().auth_minecraft_token(auth_token).await
 }
 
-pub async fn validate_token(auth: &mut Account) -> Result<bool, AuthError> {
-    let start = std::time::SystemTime::now();
-    let now = start
+fn now() -> u64 {
+    std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .expect("Time went backwards")
-        .as_secs();
+        .as_secs()
+}
+
+/// Refreshes the tokens of the account, and of the identity owning it, which can no longer be used.
+/// Returns whether any of them has been updated.
+pub async fn validate_token(
+    account: &mut Account,
+    identity: Option<&mut MicrosoftIdentity>,
+) -> Result<bool, AuthError> {
+    AccountProvider::of(account).validate(account, identity).await
+}
+
+pub async fn validate_identity(
+    account: &mut Account,
+    identity: &mut MicrosoftIdentity,
+) -> Result<bool, AuthError> {
+    let identity_updated = refresh_identity(identity, 0).await?;
+    let account_updated = refresh_profiles(std::slice::from_mut(account), identity, 0).await?;
+
+    Ok(identity_updated || account_updated)
+}
+
+/// Refreshes the OAuth token of the identity if it expires within `margin` seconds.
+/// Returns whether the identity has been updated.
+pub async fn refresh_identity(
+    identity: &mut MicrosoftIdentity,
+    margin: u64,
+) -> Result<bool, AuthError> {
+    let now = now();
 
-    let mut update = false;
+    if now + margin < identity.auth_exp_time {
+        return Ok(false);
+    }
+
+    let new_token = refresh_oauth(&identity.auth).await?;
+    identity.auth_exp_time = now + new_token.expires_in;
+    identity.auth = new_token;
+
+    Ok(true)
+}
 
-    if now >= auth.auth_exp_time {
-        let new_token = refresh_oauth(&auth.auth).await?;
-        auth.auth = new_token;
-        update = true;
+/// Refreshes the Minecraft token of the profiles owned by the identity if any of them
+/// expires within `margin` seconds. The token belongs to the identity, so every profile
+/// gets it, and the one it was issued for gets its details updated as well.
+/// Returns whether the accounts have been updated.
+pub async fn refresh_profiles(
+    accounts: &mut [Account],
+    identity: &mut MicrosoftIdentity,
+    margin: u64,
+) -> Result<bool, AuthError> {
+    let now = now();
+
+    if accounts.iter().all(|account| now + margin < account.mc_exp_time) {
+        return Ok(false);
     }
 
-    if now >= auth.mc_exp_time {
-        let (minecraft_token, minecraft_profile) = auth_minecraft_token(&auth.auth).await?;
-        auth.mc = minecraft_token;
-        auth.profile = minecraft_profile;
-        update = true;
+    let (minecraft_token, minecraft_profile, claims) = auth_minecraft_token(&identity.auth).await?;
+    let mc_exp_time = now + minecraft_token.expires_in;
+
+    for account in accounts.iter_mut() {
+        if account.profile.id == minecraft_profile.id {
+            account.profile = minecraft_profile.clone();
+        }
+
+        account.mc = minecraft_token.clone();
+        account.mc_exp_time = mc_exp_time;
+        account.needs_login = false;
     }
 
-    Ok(update)
+    identity.apply_claims(claims);
+
+    Ok(true)
 }
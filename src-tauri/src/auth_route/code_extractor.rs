@@ -45,12 +45,12 @@ impl CodeExtractor {
             .eval(format!("location.replace('{}')", auth_url).as_str())
             .ok();
 
-        let port = start(|url| {
+        let session = start(|url| {
             println!("Callback found: {}", url);
         })
         .unwrap();
 
-        Self::new(window, &format!("localhost:{}", port))
+        Self::new(window, &format!("localhost:{}", session.port))
     }
 
     pub async fn fetch(&self) -> Option<CodeToken> {
@@ -67,7 +67,7 @@ impl CodeExtractor {
 
         let event_handler = self.window.listen("code", move |ev| {
             if let Some(code) = ev.payload() {
-                if CodeToken::uri_includes_code(code) {
+                if CodeToken::uri_includes_response(code) {
                     if let Ok(mut state) = code_state.try_lock() {
                         stop_state.store(true, Ordering::Relaxed);
                         state.replace(code.to_string());
@@ -1,97 +1,166 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, Window, WindowBuilder, WindowEvent};
+use tokio::sync::mpsc;
+
+use crate::{
+    auth_route::{auth_process, completion, utils},
+    locale::Language,
+    oauth_plugin::{cancel, start_deferred, Completion, OauthConfig},
 };
 
-use regex::Regex;
-use tauri::{AppHandle, WindowBuilder};
+use super::{errors::AuthError, tokens::CodeToken};
 
-use crate::{auth_route::auth_process, oauth_plugin::start};
+const WINDOW_LABEL: &str = "abcex";
+/// The completion page closes itself after 3 seconds, the window is only
+/// closed from here if that didn't work.
+const PAGE_CLOSE_DELAY: Duration = Duration::from_secs(4);
 
-use super::errors::AuthError;
+enum LoginEvent {
+    Redirect(String, Completion),
+    Closed,
+}
 
-const CODE_PARAMS_REGEX: &str = r"[&?]((\w+)=([\w\d\.-]+))";
+/// Redirect of the provider, whose page is held until the login is known to have succeeded.
+pub struct LoginRedirect {
+    pub code: String,
+    /// Needed to exchange the code.
+    pub redirect_uri: String,
+    completion: Option<Completion>,
+    window: Window,
+}
+
+impl LoginRedirect {
+    /// Shows the success page, to be called once the code has been exchanged.
+    pub fn succeed(mut self) {
+        if let Some(completion) = self.completion.take() {
+            completion.complete(true);
+        }
+    }
+}
+
+impl Drop for LoginRedirect {
+    /// Shows the failure page unless the login succeeded, then closes the window
+    /// once the page had the time to close itself.
+    fn drop(&mut self) {
+        if let Some(completion) = self.completion.take() {
+            completion.complete(false);
+        }
+
+        let window = self.window.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(PAGE_CLOSE_DELAY).await;
+            window.close().ok();
+        });
+    }
+}
 
-/// Starts the code listener and returns the code and the state
-/// This will block the current thread until the code is received after the user
-/// has logged in inside the window which is opened by this function
+/// Starts the code listener and returns the redirect of the provider
+/// once the user has logged in inside the window which is opened by this function.
 ///
-/// The third paramter returned is the redirect uri which is used to listen for the code
-/// You might want to have this parameter to verify the integrity of the code
+/// The login window shows the completion page once the returned redirect is
+/// marked as succeeded, or dropped, see [`LoginRedirect`].
 ///
+/// The login is cancelled when the window is closed or after `timeout`,
+/// in both cases the localhost server is shut down.
+///
+/// The window title and the page shown after the redirect use `language`.
 ///
 /// # Example
 /// ```
 /// #[tauri::command]
 /// async fn login(app_handle: AppHandle)  {
-///     let (code, redirect_uri) = code_listener::create(&app_handle).await;
+///     let login = code_listener::create(&app_handle, Duration::from_secs(300), Language::English).await?;
+///     exchange(&login.code, &login.redirect_uri).await?;
+///     login.succeed();
 /// }
 /// ```
-pub async fn create(app_handle: &AppHandle) -> Result<(String, String), AuthError> {
-    let code_state = Arc::new(RwLock::new(None));
-
-    // should start be async?
-    let copy = code_state.clone();
-    let port = start(move |url| {
-        copy.write().unwrap().replace(url);
+pub async fn create(
+    app_handle: &AppHandle,
+    timeout: Duration,
+    language: Language,
+) -> Result<LoginRedirect, AuthError> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    // The server only hands over the redirect carrying it.
+    let state = utils::next_state();
+
+    let config = OauthConfig {
+        ports: None,
+        response: Some(completion::render(language, true).into()),
+        failure_response: Some(completion::render(language, false).into()),
+        path: None,
+        state: Some(state.clone()),
+    };
+
+    let redirect_sender = sender.clone();
+    let session = start_deferred(config, move |url, completion| {
+        redirect_sender.send(LoginEvent::Redirect(url, completion)).ok();
     })
-    .unwrap();
+    .map_err(|err| AuthError::OAuthError(err.to_string()))?;
 
-    let redirect_uri = format!("http://localhost:{}", port);
-    let (url, state) = auth_process::create_authentication_url(&redirect_uri);
+    let port = session.port;
+    let redirect_uri = session.redirect_uri;
+    let url = auth_process::create_authentication_url(&redirect_uri, &state);
+
+    // A window left behind by a previous attempt would prevent us from opening a new one.
+    if let Some(window) = app_handle.get_window(WINDOW_LABEL) {
+        window.close().ok();
+    }
 
     let window = WindowBuilder::new(
         app_handle,
-        "abcex",
+        WINDOW_LABEL,
         tauri::WindowUrl::External(url.parse().unwrap()),
     )
-    .title("Iniciar sesión")
+    .title(language.catalog().login_window_title)
     .build()
-    .unwrap();
-
-    loop {
-        let state = code_state.read().unwrap();
-        if state.is_some() {
-            break;
+    .map_err(|err| {
+        cancel(port).ok();
+        AuthError::OAuthError(err.to_string())
+    })?;
+
+    window.on_window_event(move |event| {
+        if let WindowEvent::CloseRequested { .. } | WindowEvent::Destroyed = event {
+            sender.send(LoginEvent::Closed).ok();
         }
-    }
+    });
 
-    window.close().unwrap();
+    let event = tokio::time::timeout(timeout, receiver.recv()).await;
 
-    let complete_uri = code_state.read().unwrap().clone().unwrap();
+    let (complete_uri, completion) = match event {
+        Ok(Some(LoginEvent::Redirect(uri, completion))) => (uri, completion),
+        Ok(Some(LoginEvent::Closed)) | Ok(None) => {
+            cancel(port).ok();
+            return Err(AuthError::Cancelled);
+        }
+        Err(_) => {
+            cancel(port).ok();
+            window.close().ok();
+            return Err(AuthError::Timeout);
+        }
+    };
+
+    // From here on, failing shows the failure page.
+    let mut login = LoginRedirect {
+        code: String::new(),
+        redirect_uri,
+        completion: Some(completion),
+        window,
+    };
 
     let (code, incoming_state) = extract_code(&complete_uri)?;
 
     if incoming_state != state {
-        Err(AuthError::OAuthError("State is malformed.".into()))
-    } else {
-        Ok((code, redirect_uri))
+        return Err(AuthError::InvalidState);
     }
+
+    login.code = code;
+    Ok(login)
 }
 
 fn extract_code(uri: &str) -> Result<(String, String), AuthError> {
-    let regex = Regex::new(CODE_PARAMS_REGEX).unwrap();
-    if regex.is_match(uri) {
-        // extract code and state
-        let extracted_params = regex.captures_iter(uri);
-
-        let mut params = extracted_params
-            .map(|param| {
-                let key = param[2].to_string();
-                let value = param[3].to_string();
-                (key, value)
-            })
-            .collect::<HashMap<String, String>>();
-
-        if !params.contains_key("code") || !params.contains_key("state") {
-            Err(AuthError::OAuthError("Invalid Parameters".into()))
-        } else {
-            let code = params.remove_entry("code").unwrap().1;
-            let state = params.remove_entry("state").unwrap().1;
-
-            Ok((code, state))
-        }
-    } else {
-        Err(AuthError::InvalidRedirectUri)
-    }
+    let token = CodeToken::from_uri(uri)?;
+
+    Ok((token.code, token.state))
 }
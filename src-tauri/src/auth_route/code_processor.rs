@@ -1,9 +1,11 @@
 use crate::auth_route::errors::AuthError;
 use crate::auth_route::tokens::{
-    CodeToken, MinecraftProfile, MinecraftToken, OAuthToken, XBLToken, XSTSToken,
+    CodeToken, MinecraftProfile, MinecraftToken, NameAvailability, NameAvailabilityResponse,
+    NameChangeEligibility, OAuthToken, SkinVariant, XBLToken, XSTSToken, XboxClaims,
 };
 use crate::auth_route::utils;
-use reqwest::Request;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Request, StatusCode};
 use serde::de::DeserializeOwned;
 use serde_json::json;
 
@@ -31,6 +33,18 @@ const XSTS_TOKEN_URL: &str = "https://xsts.auth.xboxlive.com/xsts/authorize";
 const MINECRAFT_TOKEN_URL: &str =
     "https://api.minecraftservices.com/authentication/login_with_xbox";
 const MINECRAFT_PROFILE_URL: &str = "https://api.minecraftservices.com/minecraft/profile";
+const MINECRAFT_RELYING_PARTY: &str = "rp://api.minecraftservices.com/";
+// Tokens for this relying party carry the xuid and gamertag in their claims.
+const XBOX_LIVE_RELYING_PARTY: &str = "http://xboxlive.com";
+const MINECRAFT_SKINS_URL: &str = "https://api.minecraftservices.com/minecraft/profile/skins";
+const MINECRAFT_ACTIVE_SKIN_URL: &str =
+    "https://api.minecraftservices.com/minecraft/profile/skins/active";
+const MINECRAFT_ACTIVE_CAPE_URL: &str =
+    "https://api.minecraftservices.com/minecraft/profile/capes/active";
+const MINECRAFT_NAME_CHANGE_URL: &str =
+    "https://api.minecraftservices.com/minecraft/profile/namechange";
+const MINECRAFT_NAME_URL: &str = "https://api.minecraftservices.com/minecraft/profile/name";
+const PROFILE_NAME_LENGTH: std::ops::RangeInclusive<usize> = 3..=16;
 
 /// Returns (uri, state)
 pub fn authenticate_url(redirect_uri: &str) -> (String, String) {
@@ -60,6 +74,17 @@ pub fn authenticate_url(redirect_uri: &str) -> (String, String) {
     (format!("{}{}", BASE_URL, params), state)
 }
 
+/// Names are part of the request path, so they are checked against
+/// what Minecraft accepts before building it.
+fn validate_profile_name(name: &str) -> Result<(), AuthError> {
+    let valid_chars = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    match valid_chars && PROFILE_NAME_LENGTH.contains(&name.len()) {
+        true => Ok(()),
+        false => Err(AuthError::InvalidProfileName(name.to_string())),
+    }
+}
+
 pub struct CodeProcessor {
     client: reqwest::Client,
 }
@@ -82,7 +107,8 @@ impl CodeProcessor {
         code: CodeToken,
     ) -> Result<(MinecraftProfile, MinecraftToken, OAuthToken), AuthError> {
         let auth_token = self.get_auth_token(code).await?;
-        let (minecraft_token, minecraft_profile) = self.auth_minecraft_token(&auth_token).await?;
+        let (minecraft_token, minecraft_profile, _) =
+            self.auth_minecraft_token(&auth_token).await?;
 
         Ok((minecraft_profile, minecraft_token, auth_token))
     }
@@ -107,13 +133,19 @@ impl CodeProcessor {
     pub async fn auth_minecraft_token(
         &self,
         auth_token: &OAuthToken,
-    ) -> Result<(MinecraftToken, MinecraftProfile), AuthError> {
+    ) -> Result<(MinecraftToken, MinecraftProfile, XboxClaims), AuthError> {
         let xbl_token = self.get_xbl_token(auth_token).await?;
-        let xsts_token = self.get_xsts_token(xbl_token).await?;
+        let claims = self
+            .get_xsts_token(&xbl_token.token, XBOX_LIVE_RELYING_PARTY)
+            .await?
+            .claims();
+        let xsts_token = self
+            .get_xsts_token(&xbl_token.token, MINECRAFT_RELYING_PARTY)
+            .await?;
         let minecraft_token = self.get_minecraft_token(xsts_token).await?;
         let minecraft_profile = self.get_minecraft_profile(&minecraft_token).await?;
 
-        Ok((minecraft_token, minecraft_profile))
+        Ok((minecraft_token, minecraft_profile, claims))
     }
 
     async fn get_auth_token(&self, code: CodeToken) -> Result<OAuthToken, AuthError> {
@@ -155,9 +187,11 @@ impl CodeProcessor {
             .await
     }
 
-    async fn get_xsts_token(&self, token: XBLToken) -> Result<XSTSToken, AuthError> {
-        let token = token.extract_token();
-
+    async fn get_xsts_token(
+        &self,
+        token: &str,
+        relying_party: &str,
+    ) -> Result<XSTSToken, AuthError> {
         let request = self
             .client
             .post(XSTS_TOKEN_URL)
@@ -166,7 +200,7 @@ impl CodeProcessor {
                     "SandboxId": "RETAIL",
                     "UserTokens": [token]
                 },
-                "RelyingParty": "rp://api.minecraftservices.com/",
+                "RelyingParty": relying_party,
                 "TokenType": "JWT"
             }))
             .build()
@@ -210,6 +244,178 @@ impl CodeProcessor {
             .await
     }
 
+    pub async fn upload_skin(
+        &self,
+        token: &MinecraftToken,
+        variant: SkinVariant,
+        skin: Vec<u8>,
+    ) -> Result<MinecraftProfile, AuthError> {
+        let file = Part::bytes(skin)
+            .file_name("skin.png")
+            .mime_str("image/png")
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        let request = self
+            .client
+            .post(MINECRAFT_SKINS_URL)
+            .bearer_auth(&token.access_token)
+            .multipart(Form::new().text("variant", variant.as_str()).part("file", file))
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_response(request, |err| AuthError::ProfileError(err))
+            .await
+    }
+
+    pub async fn set_skin_url(
+        &self,
+        token: &MinecraftToken,
+        variant: SkinVariant,
+        url: &str,
+    ) -> Result<MinecraftProfile, AuthError> {
+        let request = self
+            .client
+            .post(MINECRAFT_SKINS_URL)
+            .bearer_auth(&token.access_token)
+            .json(&json!({ "variant": variant.as_str(), "url": url }))
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_response(request, |err| AuthError::ProfileError(err))
+            .await
+    }
+
+    pub async fn reset_skin(&self, token: &MinecraftToken) -> Result<MinecraftProfile, AuthError> {
+        let request = self
+            .client
+            .delete(MINECRAFT_ACTIVE_SKIN_URL)
+            .bearer_auth(&token.access_token)
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_response(request, |err| AuthError::ProfileError(err))
+            .await
+    }
+
+    pub async fn show_cape(
+        &self,
+        token: &MinecraftToken,
+        cape_id: &str,
+    ) -> Result<MinecraftProfile, AuthError> {
+        let request = self
+            .client
+            .put(MINECRAFT_ACTIVE_CAPE_URL)
+            .bearer_auth(&token.access_token)
+            .json(&json!({ "capeId": cape_id }))
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_response(request, |err| AuthError::ProfileError(err))
+            .await
+    }
+
+    pub async fn hide_cape(&self, token: &MinecraftToken) -> Result<MinecraftProfile, AuthError> {
+        let request = self
+            .client
+            .delete(MINECRAFT_ACTIVE_CAPE_URL)
+            .bearer_auth(&token.access_token)
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_response(request, |err| AuthError::ProfileError(err))
+            .await
+    }
+
+    pub async fn name_change_eligibility(
+        &self,
+        token: &MinecraftToken,
+    ) -> Result<NameChangeEligibility, AuthError> {
+        let request = self
+            .client
+            .get(MINECRAFT_NAME_CHANGE_URL)
+            .bearer_auth(&token.access_token)
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_response(request, |err| AuthError::ProfileError(err))
+            .await
+    }
+
+    pub async fn name_availability(
+        &self,
+        token: &MinecraftToken,
+        name: &str,
+    ) -> Result<NameAvailability, AuthError> {
+        validate_profile_name(name)?;
+
+        let request = self
+            .client
+            .get(format!("{}/{}/available", MINECRAFT_NAME_URL, name))
+            .bearer_auth(&token.access_token)
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_name_response::<NameAvailabilityResponse>(request, name)
+            .await
+            .map(|response| response.status)
+    }
+
+    pub async fn change_name(
+        &self,
+        token: &MinecraftToken,
+        name: &str,
+    ) -> Result<MinecraftProfile, AuthError> {
+        validate_profile_name(name)?;
+
+        let request = self
+            .client
+            .put(format!("{}/{}", MINECRAFT_NAME_URL, name))
+            .bearer_auth(&token.access_token)
+            .build()
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        self.extract_name_response(request, name).await
+    }
+
+    /// Like `extract_response` but telling apart the errors of the name endpoints.
+    async fn extract_name_response<T>(&self, request: Request, name: &str) -> Result<T, AuthError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return response
+                .json::<T>()
+                .await
+                .map_err(|err| AuthError::ProfileError(err.to_string()));
+        }
+
+        let err = response
+            .text()
+            .await
+            .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => Err(AuthError::RateLimited),
+            StatusCode::BAD_REQUEST => Err(AuthError::InvalidUsername(name.to_string())),
+            StatusCode::FORBIDDEN if err.contains("DUPLICATE") => {
+                Err(AuthError::NameUnavailable(name.to_string()))
+            }
+            StatusCode::FORBIDDEN => Err(AuthError::NameChangeNotAllowed),
+            _ => Err(AuthError::ProfileError(format!(
+                "Invalid response {}: {}",
+                status.as_str(),
+                err
+            ))),
+        }
+    }
+
     async fn extract_response<T, O>(&self, request: Request, err_map: O) -> Result<T, AuthError>
     where
         T: DeserializeOwned,
@@ -235,10 +441,39 @@ impl CodeProcessor {
                 .await
                 .map_err(|err| err_map(err.to_string()))?;
             let status_code = status.as_str();
-            Err(err_map(format!(
-                "Invalid response {}: {}",
-                status_code, err
-            )))
+            let err = err_map(format!("Invalid response {}: {}", status_code, err));
+
+            // Revoked or expired refresh tokens are answered with invalid_grant.
+            match status {
+                StatusCode::UNAUTHORIZED => Err(AuthError::SessionRejected(err.to_string())),
+                StatusCode::BAD_REQUEST if err.to_string().contains("invalid_grant") => {
+                    Err(AuthError::SessionRejected(err.to_string()))
+                }
+                _ => Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_profile_names() {
+        for name in ["abc", "Notch", "jeb_", "sixteen_chars_16"] {
+            assert!(validate_profile_name(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_profile_names() {
+        for name in ["", "ab", "seventeen_chars17", "../skins", "a/b?c", "name#1", "with space", "ünï"] {
+            assert!(
+                matches!(validate_profile_name(name), Err(AuthError::InvalidProfileName(_))),
+                "{}",
+                name
+            );
         }
     }
 }
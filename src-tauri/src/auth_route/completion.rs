@@ -0,0 +1,31 @@
+use crate::locale::Language;
+
+const COMPLETION_PAGE: &str = include_str!("pages/completion.html");
+
+/// Page shown in the login window once the provider redirects back to the launcher.
+pub fn render(language: Language, succeeded: bool) -> String {
+    let catalog = language.catalog();
+
+    let (title, message, icon, color) = match succeeded {
+        true => (
+            catalog.login_success_title,
+            catalog.login_success_message,
+            "&#10003;",
+            "#16a34a",
+        ),
+        false => (
+            catalog.login_failure_title,
+            catalog.login_failure_message,
+            "&#10007;",
+            "#dc2626",
+        ),
+    };
+
+    COMPLETION_PAGE
+        .replace("{{lang}}", catalog.code)
+        .replace("{{title}}", title)
+        .replace("{{message}}", message)
+        .replace("{{closing}}", catalog.login_closing_message)
+        .replace("{{icon}}", icon)
+        .replace("{{color}}", color)
+}
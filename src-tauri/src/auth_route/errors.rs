@@ -22,5 +22,78 @@ pub enum AuthError {
     InvalidState,
 
     #[error("Minecraft profile could not be retrieved")]
-    MissingMinecraftProfile
-}
\ No newline at end of file
+    MissingMinecraftProfile,
+
+    #[error("Offline accounts are disabled")]
+    OfflineAccountsDisabled,
+
+    #[error("No account is selected")]
+    NoElectedAccount,
+
+    #[error("The Microsoft sign-in of this account is missing, please sign in again")]
+    MissingIdentity,
+
+    #[error("Invalid username: {0}")]
+    InvalidUsername(String),
+
+    #[error("Unknown account: {0}")]
+    UnknownAccount(String),
+
+    #[error("This action is not available for offline accounts")]
+    OfflineAccount,
+
+    #[error("This action is only available for Microsoft accounts")]
+    NotMicrosoftAccount,
+
+    #[error("Failed Minecraft Profile: {0}")]
+    ProfileError(String),
+
+    #[error("Invalid skin: {0}")]
+    InvalidSkin(String),
+
+    #[error("Invalid profile name: {0}")]
+    InvalidProfileName(String),
+
+    #[error("The name {0} is already taken")]
+    NameUnavailable(String),
+
+    #[error("The name can't be changed yet")]
+    NameChangeNotAllowed,
+
+    #[error("Too many requests, try again later")]
+    RateLimited,
+
+    #[error("Login was cancelled")]
+    Cancelled,
+
+    #[error("Login timed out")]
+    Timeout,
+
+    #[error("Access was denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Login failed ({0}): {1}")]
+    ProviderError(String, String),
+
+    #[error("Failed Yggdrasil: {0}")]
+    YggdrasilError(String),
+
+    #[error("Could not save accounts: {0}")]
+    SaveError(String),
+
+    #[error("The session was rejected: {0}")]
+    SessionRejected(String),
+}
+
+impl AuthError {
+    /// Whether the service refused the tokens, so the user has to sign in again.
+    /// Other errors, such as network failures, may go away by retrying later.
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            AuthError::SessionRejected(_)
+                | AuthError::MissingMinecraftProfile
+                | AuthError::MissingIdentity
+        )
+    }
+}
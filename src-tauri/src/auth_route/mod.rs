@@ -3,7 +3,15 @@ mod auth_process;
 pub mod auther;
 pub mod code_extractor;
 pub mod code_listener;
+mod completion;
 mod code_processor;
 pub mod errors;
+pub mod offline;
+pub mod profile;
+pub mod provider;
+pub mod refresher;
+pub mod signout;
+pub mod skins;
 pub mod tokens;
 mod utils;
+pub mod yggdrasil;
@@ -0,0 +1,82 @@
+use md5::{Digest, Md5};
+
+use crate::auth_route::accounts::{Account, AccountKind};
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::tokens::{MinecraftProfile, MinecraftToken};
+
+const MAX_USERNAME_LENGTH: usize = 16;
+
+/// Creates a non-premium account for the given username.
+///
+/// The id is derived the same way vanilla does for offline players, so the
+/// player keeps its data across LAN worlds and offline mode servers.
+pub fn create_account(username: &str) -> Result<Account, AuthError> {
+    let valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if username.is_empty() || username.len() > MAX_USERNAME_LENGTH || !valid_chars {
+        return Err(AuthError::InvalidUsername(username.to_string()));
+    }
+
+    let profile = MinecraftProfile {
+        id: offline_uuid(username),
+        name: username.to_string(),
+        skins: vec![],
+        capes: vec![],
+    };
+
+    // Placeholder tokens never expire, so they are never refreshed.
+    Ok(Account::new(
+        AccountKind::Offline,
+        profile,
+        None,
+        MinecraftToken::placeholder(username),
+        u64::MAX,
+    ))
+}
+
+/// Name based (version 3) UUID of `OfflinePlayer:<username>`, without dashes
+/// like the ids returned by the Minecraft profile endpoint.
+fn offline_uuid(username: &str) -> String {
+    let mut hash: [u8; 16] = Md5::digest(format!("OfflinePlayer:{}", username)).into();
+
+    hash[6] = (hash[6] & 0x0f) | 0x30;
+    hash[8] = (hash[8] & 0x3f) | 0x80;
+
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        assert_eq!(offline_uuid("Notch"), "b50ad385829d3141a2167e7d7539ba7f");
+    }
+
+    #[test]
+    fn offline_uuid_is_case_sensitive() {
+        assert_ne!(offline_uuid("notch"), offline_uuid("Notch"));
+    }
+
+    #[test]
+    fn rejects_invalid_usernames() {
+        for username in ["", "with space", "seventeen_chars__", "dash-name"] {
+            assert!(matches!(
+                create_account(username),
+                Err(AuthError::InvalidUsername(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn creates_account_with_offline_uuid() {
+        let account = create_account("Notch").unwrap();
+
+        assert_eq!(account.kind, AccountKind::Offline);
+        assert_eq!(account.profile.id, offline_uuid("Notch"));
+        assert_eq!(account.profile.name, "Notch");
+    }
+}
@@ -0,0 +1,90 @@
+use tauri::{AppHandle, Manager};
+use tracing::error;
+
+use crate::auth_route::accounts::{Account, AccountKind};
+use crate::auth_route::auther;
+use crate::auth_route::code_processor::CodeProcessor;
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::provider::AccountProvider;
+use crate::auth_route::tokens::{MinecraftProfile, NameAvailability, NameChangeEligibility};
+use crate::Storage;
+
+/// Returns the stored account with tokens valid to call the profile endpoints.
+pub(super) async fn prepare_account(handle: &AppHandle, id: &str) -> Result<Account, AuthError> {
+    let storage = handle.state::<Storage>().inner().extract();
+
+    let (mut account, mut identity) = {
+        let storage = storage.read().unwrap();
+        storage.settings.accounts.contents.session(id)?
+    };
+
+    if account.kind == AccountKind::Offline {
+        return Err(AuthError::OfflineAccount);
+    }
+
+    if !AccountProvider::of(&account).has_profile_api() {
+        return Err(AuthError::NotMicrosoftAccount);
+    }
+
+    if auther::validate_token(&mut account, identity.as_mut()).await? {
+        let mut storage = storage.write().unwrap();
+        storage.settings.accounts.contents.store_session(account.clone(), identity);
+        if let Err(err) = storage.settings.accounts.save() {
+            error!("Could not save refreshed account: {}", err);
+        }
+    }
+
+    Ok(account)
+}
+
+/// Stores the profile returned by the endpoints and caches its textures.
+pub(super) async fn update_profile(handle: &AppHandle, profile: MinecraftProfile) -> MinecraftProfile {
+    let storage = handle.state::<Storage>().inner().extract();
+
+    let textures = {
+        let mut storage = storage.write().unwrap();
+        let store = &mut storage.settings.accounts.contents;
+
+        if let Some(account) = store.accounts.iter_mut().find(|it| it.profile.id == profile.id) {
+            account.profile = profile.clone();
+        }
+
+        if let Err(err) = storage.settings.accounts.save() {
+            error!("Could not save account profile: {}", err);
+        }
+
+        storage.textures.clone()
+    };
+
+    textures.textures_of(&profile).await;
+
+    profile
+}
+
+pub async fn name_change_eligibility(
+    handle: &AppHandle,
+    id: &str,
+) -> Result<NameChangeEligibility, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    CodeProcessor::new().name_change_eligibility(&account.mc).await
+}
+
+pub async fn name_availability(
+    handle: &AppHandle,
+    id: &str,
+    name: &str,
+) -> Result<NameAvailability, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    CodeProcessor::new().name_availability(&account.mc, name).await
+}
+
+pub async fn change_name(
+    handle: &AppHandle,
+    id: &str,
+    name: &str,
+) -> Result<MinecraftProfile, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new().change_name(&account.mc, name).await?;
+
+    Ok(update_profile(handle, profile).await)
+}
@@ -0,0 +1,58 @@
+use crate::auth_route::accounts::{Account, AccountKind, MicrosoftIdentity};
+use crate::auth_route::auther;
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::yggdrasil;
+
+/// Service issuing the tokens of an account, which knows how to keep them valid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountProvider {
+    Microsoft,
+    Offline,
+    Yggdrasil,
+}
+
+impl AccountProvider {
+    pub fn of(account: &Account) -> Self {
+        match account.kind {
+            AccountKind::Microsoft => AccountProvider::Microsoft,
+            AccountKind::Offline => AccountProvider::Offline,
+            AccountKind::Yggdrasil => AccountProvider::Yggdrasil,
+        }
+    }
+
+    /// Refreshes the tokens of the account which can no longer be used.
+    /// Returns whether the account, or the identity owning it, has been updated.
+    pub async fn validate(
+        &self,
+        account: &mut Account,
+        identity: Option<&mut MicrosoftIdentity>,
+    ) -> Result<bool, AuthError> {
+        match self {
+            AccountProvider::Microsoft => match identity {
+                Some(identity) => auther::validate_identity(account, identity).await,
+                // The tokens can't be refreshed without the identity owning them.
+                None => Err(AuthError::MissingIdentity),
+            },
+            // Placeholder tokens are never refreshed.
+            AccountProvider::Offline => Ok(false),
+            AccountProvider::Yggdrasil => yggdrasil::validate_account(account).await,
+        }
+    }
+
+    /// Invalidates the session of the account with the service which issued it.
+    /// Returns false if the provider doesn't support it.
+    pub async fn invalidate(&self, account: &Account) -> Result<bool, AuthError> {
+        match self {
+            // Microsoft doesn't offer a way to revoke consumer tokens,
+            // forgetting them is all we can do.
+            AccountProvider::Microsoft => Ok(false),
+            AccountProvider::Offline => Ok(false),
+            AccountProvider::Yggdrasil => yggdrasil::invalidate_account(account).await.map(|_| true),
+        }
+    }
+
+    /// Whether the Minecraft services profile endpoints (skins, capes, names) work with its tokens.
+    pub fn has_profile_api(&self) -> bool {
+        *self == AccountProvider::Microsoft
+    }
+}
@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tracing::{error, info, warn};
+
+use crate::auth_route::accounts::AccountKind;
+use crate::auth_route::{auther, yggdrasil};
+use crate::Storage;
+
+/// How often the stored accounts are reviewed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Tokens expiring within this many seconds are refreshed ahead of time,
+/// it must be larger than the interval so no token expires between reviews.
+const REFRESH_MARGIN: u64 = 10 * 60;
+
+#[derive(Serialize, Clone)]
+enum AccountState {
+    REFRESHED,
+    EXPIRED,
+}
+
+#[derive(Serialize, Clone)]
+struct AccountStateEvent {
+    id: String,
+    state: AccountState,
+}
+
+/// Spawns the task which keeps the tokens of every stored identity and account fresh,
+/// including the sessions of Yggdrasil accounts.
+///
+/// Updated tokens are persisted right away. Accounts whose tokens are rejected
+/// are flagged with `needs_login` and an `accounts:state` event is emitted,
+/// so the UI can ask the user to sign in again. Other failures are retried later.
+pub fn start(handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            refresh_accounts(&handle).await;
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+async fn refresh_accounts(handle: &AppHandle) {
+    let storage = handle.state::<Storage>().inner().extract();
+
+    // We work over a copy so the lock is not held while talking to the network,
+    //   the results are merged back into whatever is stored by then.
+    let (accounts, identities) = {
+        let storage = storage.read().unwrap();
+        let store = &storage.settings.accounts.contents;
+        (store.accounts.clone(), store.identities.clone())
+    };
+
+    for previous_identity in identities {
+        let mut identity = previous_identity.clone();
+
+        // Every profile of the identity needs a valid OAuth token to be refreshed.
+        let identity_error = match auther::refresh_identity(&mut identity, REFRESH_MARGIN).await {
+            Ok(updated) => {
+                if updated {
+                    info!("Refreshed Microsoft identity {}", identity.id);
+                }
+                None
+            }
+            Err(err) => {
+                warn!("Could not refresh Microsoft identity {}: {}", identity.id, err);
+                Some(err.is_rejection())
+            }
+        };
+
+        let previous = accounts
+            .iter()
+            .filter(|it| it.identity.as_ref() == Some(&identity.id))
+            .collect::<Vec<_>>();
+        let mut owned = previous.iter().map(|it| (*it).clone()).collect::<Vec<_>>();
+
+        // The profiles share the Minecraft token of the identity, so they are refreshed together.
+        let result = match identity_error {
+            Some(rejected) => Err(rejected),
+            None => auther::refresh_profiles(&mut owned, &mut identity, REFRESH_MARGIN)
+                .await
+                .map_err(|err| {
+                    warn!("Could not refresh the profiles of {}: {}", identity.id, err);
+                    err.is_rejection()
+                }),
+        };
+
+        let state = match result {
+            Ok(false) => None,
+            Ok(true) => Some(AccountState::REFRESHED),
+            // Network failures and outages are retried on the next review.
+            Err(false) => None,
+            Err(true) => Some(AccountState::EXPIRED),
+        };
+
+        let mut events = vec![];
+
+        if let Some(state) = state {
+            let mut storage = storage.write().unwrap();
+            let store = &mut storage.settings.accounts.contents;
+
+            for (previous, mut account) in previous.into_iter().zip(owned) {
+                match state {
+                    AccountState::REFRESHED => info!("Refreshed tokens of {}", account.profile.name),
+                    AccountState::EXPIRED if account.needs_login => continue,
+                    AccountState::EXPIRED => account.needs_login = true,
+                }
+
+                let id = account.profile.id.clone();
+
+                if store.merge_refreshed(previous, account) {
+                    events.push(AccountStateEvent {
+                        id,
+                        state: state.clone(),
+                    });
+                }
+            }
+        }
+
+        // The identity is always merged, its tokens could have been
+        //   rotated even if refreshing one of its profiles failed afterwards.
+        {
+            let mut storage = storage.write().unwrap();
+            storage
+                .settings
+                .accounts
+                .contents
+                .merge_refreshed_identity(&previous_identity, identity);
+
+            if let Err(err) = storage.settings.accounts.save() {
+                error!("Could not save refreshed accounts: {}", err);
+            }
+        }
+
+        for event in events {
+            handle.emit_all("accounts:state", event).ok();
+        }
+    }
+
+    let sessions = accounts.iter().filter(|it| it.kind == AccountKind::Yggdrasil);
+
+    for previous in sessions {
+        let mut account = previous.clone();
+
+        let state = match yggdrasil::validate_account(&mut account).await {
+            Ok(false) => continue,
+            Ok(true) => {
+                info!("Refreshed Yggdrasil session of {}", account.profile.name);
+                AccountState::REFRESHED
+            }
+            Err(err) => {
+                warn!("Could not refresh {}: {}", account.profile.name, err);
+
+                // Network failures and outages are retried on the next review.
+                if !err.is_rejection() || account.needs_login {
+                    continue;
+                }
+
+                account.needs_login = true;
+                AccountState::EXPIRED
+            }
+        };
+
+        let id = account.profile.id.clone();
+
+        let merged = {
+            let mut storage = storage.write().unwrap();
+            let merged = storage
+                .settings
+                .accounts
+                .contents
+                .merge_refreshed(previous, account);
+
+            if merged {
+                if let Err(err) = storage.settings.accounts.save() {
+                    error!("Could not save refreshed accounts: {}", err);
+                }
+            }
+
+            merged
+        };
+
+        if merged {
+            handle
+                .emit_all("accounts:state", AccountStateEvent { id, state })
+                .ok();
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, WindowBuilder};
+use tracing::{error, info, warn};
+
+use crate::auth_route::accounts::AccountKind;
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::provider::AccountProvider;
+use crate::files::audit::{AuditAction, AuditEntry};
+use crate::Storage;
+
+/// Pages ending the session of the login domains, which should clear their cookies.
+const LOGOUT_URLS: &[&str] = &[
+    "https://login.microsoftonline.com/consumers/oauth2/v2.0/logout",
+    "https://login.live.com/logout.srf",
+];
+const LOGOUT_WINDOW_LABEL: &str = "signout";
+/// Time given to the logout pages to load before their windows are closed.
+const LOGOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// Signs the account out: its session is invalidated where the provider supports it,
+/// its tokens are forgotten and the logout pages of the login domains are requested,
+/// so the next login prompts for the credentials again.
+pub async fn sign_out(handle: &AppHandle, id: &str) -> Result<(), AuthError> {
+    let storage = handle.state::<Storage>().inner().extract();
+
+    let account = {
+        let storage = storage.read().unwrap();
+        storage.settings.accounts.contents.find(id)?.clone()
+    };
+
+    // Failing to reach the service shouldn't keep the tokens stored.
+    let invalidated = match AccountProvider::of(&account).invalidate(&account).await {
+        Ok(invalidated) => invalidated,
+        Err(err) => {
+            warn!("Could not invalidate session of {}: {}", account.profile.name, err);
+            false
+        }
+    };
+
+    let (forgot_refresh_token, audit) = {
+        let mut storage = storage.write().unwrap();
+        let store = &mut storage.settings.accounts.contents;
+
+        let removed = store.remove(id)?;
+
+        // The identity is only dropped with its last profile.
+        let forgot_refresh_token = match &removed.identity {
+            Some(identity) => store.identity(identity).is_none(),
+            None => false,
+        };
+
+        storage
+            .settings
+            .accounts
+            .save()
+            .map_err(|err| AuthError::SaveError(err.to_string()))?;
+
+        (forgot_refresh_token, storage.audit.clone())
+    };
+
+    let logout_requested = match account.kind {
+        AccountKind::Microsoft => request_logout(handle).await,
+        _ => false,
+    };
+
+    info!("Signed out {}", account.profile.name);
+
+    let entry = AuditEntry {
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs(),
+        action: AuditAction::SIGNOUT,
+        account: account.profile.id,
+        name: account.profile.name,
+        invalidated,
+        forgot_refresh_token,
+        logout_requested,
+    };
+
+    if let Err(err) = audit.record(&entry) {
+        error!("Could not record sign out: {}", err);
+    }
+
+    Ok(())
+}
+
+/// Tauri can't reach the cookie store of the webview, so the logout pages are
+/// loaded in hidden windows which share it with the login window.
+/// Returns whether every page was requested, whether they actually cleared
+/// the cookies can't be observed from here.
+async fn request_logout(handle: &AppHandle) -> bool {
+    let mut windows = vec![];
+
+    for (i, url) in LOGOUT_URLS.iter().enumerate() {
+        let label = format!("{}-{}", LOGOUT_WINDOW_LABEL, i);
+
+        // Left behind by a sign out still in progress.
+        if let Some(window) = handle.get_window(&label) {
+            window.close().ok();
+        }
+
+        let window = WindowBuilder::new(
+            handle,
+            label,
+            tauri::WindowUrl::External(url.parse().unwrap()),
+        )
+        .visible(false)
+        .build();
+
+        match window {
+            Ok(window) => windows.push(window),
+            Err(err) => warn!("Could not open {}: {}", url, err),
+        }
+    }
+
+    tokio::time::sleep(LOGOUT_GRACE).await;
+
+    let requested = windows.len() == LOGOUT_URLS.len();
+
+    for window in windows {
+        window.close().ok();
+    }
+
+    requested
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::auth_route::code_processor::CodeProcessor;
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::profile::{prepare_account, update_profile};
+use crate::auth_route::tokens::{MinecraftProfile, SkinVariant};
+use crate::auth_route::utils;
+use crate::Storage;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const SKIN_WIDTH: u32 = 64;
+const SKIN_HEIGHTS: [u32; 2] = [64, 32];
+
+/// Local copy of the skin and cape images, so the account picker doesn't
+/// have to download them every time it is shown.
+#[derive(Clone, Debug)]
+pub struct TextureCache {
+    path: PathBuf,
+}
+
+/// Paths of the cached textures currently worn by an account.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct AccountTextures {
+    pub skin: Option<PathBuf>,
+    pub cape: Option<PathBuf>,
+}
+
+impl TextureCache {
+    pub fn new(base: &PathBuf) -> TextureCache {
+        TextureCache {
+            path: base.join("cache").join("textures"),
+        }
+    }
+
+    pub fn ensure_exists(&self) -> std::io::Result<()> {
+        fs::create_dir_all(&self.path)
+    }
+
+    /// Texture urls end with the hash of the image, which we use as file name.
+    fn texture_path(&self, url: &str) -> Option<PathBuf> {
+        url.rsplit('/')
+            .next()
+            .filter(|hash| !hash.is_empty() && hash.chars().all(|c| c.is_ascii_alphanumeric()))
+            .map(|hash| self.path.join(format!("{}.png", hash)))
+    }
+
+    async fn fetch(&self, url: &str) -> Option<PathBuf> {
+        let path = self.texture_path(url)?;
+
+        if path.exists() {
+            return Some(path);
+        }
+
+        match download(url, &path).await {
+            Ok(_) => Some(path),
+            Err(err) => {
+                warn!("Could not cache texture {}: {}", url, err);
+                None
+            }
+        }
+    }
+
+    pub async fn textures_of(&self, profile: &MinecraftProfile) -> AccountTextures {
+        let skin = match profile.active_skin() {
+            Some(skin) => self.fetch(&skin.url).await,
+            None => None,
+        };
+
+        let cape = match profile.active_cape() {
+            Some(cape) => self.fetch(&cape.url).await,
+            None => None,
+        };
+
+        AccountTextures { skin, cape }
+    }
+}
+
+/// Writes the texture next to `path` first, and only moves it in place once complete,
+/// so an interrupted download never leaves a truncated texture in the cache.
+async fn download(url: &str, path: &Path) -> Result<(), AuthError> {
+    let bytes = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| AuthError::ProfileError(err.to_string()))?
+        .bytes()
+        .await
+        .map_err(|err| AuthError::ProfileError(err.to_string()))?;
+
+    let partial = path.with_extension(format!("{}.part", utils::next_state()));
+
+    let result = fs::write(&partial, &bytes).and_then(|_| fs::rename(&partial, path));
+    if result.is_err() {
+        fs::remove_file(&partial).ok();
+    }
+
+    result.map_err(|err| AuthError::ProfileError(err.to_string()))
+}
+
+/// Checks the image is a PNG with the size of a modern (64x64) or legacy (64x32) skin.
+fn validate_skin(skin: &[u8]) -> Result<(), AuthError> {
+    // The IHDR chunk always comes first, right after the signature.
+    if skin.len() < 24 || !skin.starts_with(PNG_SIGNATURE) || &skin[12..16] != b"IHDR" {
+        return Err(AuthError::InvalidSkin("The file is not a PNG image".into()));
+    }
+
+    let width = u32::from_be_bytes([skin[16], skin[17], skin[18], skin[19]]);
+    let height = u32::from_be_bytes([skin[20], skin[21], skin[22], skin[23]]);
+
+    if width != SKIN_WIDTH || !SKIN_HEIGHTS.contains(&height) {
+        return Err(AuthError::InvalidSkin(format!(
+            "Expected a 64x64 or 64x32 image, got {}x{}",
+            width, height
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn list_textures(handle: &AppHandle, id: &str) -> Result<MinecraftProfile, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new()
+        .get_minecraft_profile(&account.mc)
+        .await?;
+
+    Ok(update_profile(handle, profile).await)
+}
+
+pub async fn upload_skin(
+    handle: &AppHandle,
+    id: &str,
+    path: PathBuf,
+    variant: SkinVariant,
+) -> Result<MinecraftProfile, AuthError> {
+    let skin = fs::read(&path).map_err(|err| AuthError::InvalidSkin(err.to_string()))?;
+    validate_skin(&skin)?;
+
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new()
+        .upload_skin(&account.mc, variant, skin)
+        .await?;
+
+    Ok(update_profile(handle, profile).await)
+}
+
+pub async fn set_skin_url(
+    handle: &AppHandle,
+    id: &str,
+    url: &str,
+    variant: SkinVariant,
+) -> Result<MinecraftProfile, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new()
+        .set_skin_url(&account.mc, variant, url)
+        .await?;
+
+    Ok(update_profile(handle, profile).await)
+}
+
+pub async fn reset_skin(handle: &AppHandle, id: &str) -> Result<MinecraftProfile, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new().reset_skin(&account.mc).await?;
+
+    Ok(update_profile(handle, profile).await)
+}
+
+pub async fn show_cape(
+    handle: &AppHandle,
+    id: &str,
+    cape_id: &str,
+) -> Result<MinecraftProfile, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new().show_cape(&account.mc, cape_id).await?;
+
+    Ok(update_profile(handle, profile).await)
+}
+
+pub async fn hide_cape(handle: &AppHandle, id: &str) -> Result<MinecraftProfile, AuthError> {
+    let account = prepare_account(handle, id).await?;
+    let profile = CodeProcessor::new().hide_cape(&account.mc).await?;
+
+    Ok(update_profile(handle, profile).await)
+}
+
+/// Cached textures of the account, downloading the missing ones.
+pub async fn cached_textures(handle: &AppHandle, id: &str) -> Result<AccountTextures, AuthError> {
+    let (profile, textures) = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let storage = storage.read().unwrap();
+
+        let profile = storage
+            .settings
+            .accounts
+            .contents
+            .find(id)?
+            .profile
+            .clone();
+
+        (profile, storage.textures.clone())
+    };
+
+    Ok(textures.textures_of(&profile).await)
+}
@@ -1,9 +1,7 @@
 use crate::auth_route::errors::AuthError;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-
-const CODE_PARAMS_REGEX: &str = r"[&?]((\w+)=([\w\d\.-]+))";
+use url::Url;
 
 pub enum Token {
     Code(CodeToken),
@@ -23,37 +21,36 @@ impl CodeToken {
         Self { code, state }
     }
 
+    /// Reads the authorization response out of the redirect uri.
+    ///
+    /// Error responses (`error` and `error_description`) sent by the provider
+    /// are turned into the matching `AuthError`.
     pub fn from_uri(uri: &str) -> Result<Self, AuthError> {
-        let regex = Regex::new(CODE_PARAMS_REGEX).unwrap();
-        if regex.is_match(uri) {
-            // extract code and state
-            let extracted_params = regex.captures_iter(uri);
-
-            let mut params = extracted_params
-                .map(|param| {
-                    let key = param[2].to_string();
-                    let value = param[3].to_string();
-                    (key, value)
-                })
-                .collect::<HashMap<String, String>>();
-
-            if !params.contains_key("code") || !params.contains_key("state") {
-                Err(AuthError::OAuthError("Invalid Parameters".into()))
-            } else {
-                let code = params.remove_entry("code").unwrap().1;
-                let state = params.remove_entry("state").unwrap().1;
-
-                let entity = Self::new(code, state);
-                Ok(entity)
-            }
-        } else {
-            Err(AuthError::InvalidRedirectUri)
+        let url = Url::parse(uri).map_err(|_| AuthError::InvalidRedirectUri)?;
+
+        let mut params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+
+        if let Some(error) = params.remove("error") {
+            let description = params.remove("error_description").unwrap_or_default();
+
+            return Err(match error.as_str() {
+                "access_denied" => AuthError::AccessDenied(description),
+                _ => AuthError::ProviderError(error, description),
+            });
+        }
+
+        match (params.remove("code"), params.remove("state")) {
+            (Some(code), Some(state)) => Ok(Self::new(code, state)),
+            _ => Err(AuthError::OAuthError("Invalid Parameters".into())),
         }
     }
 
-    pub fn uri_includes_code(uri: &str) -> bool {
-        let regex = Regex::new(CODE_PARAMS_REGEX).unwrap();
-        regex.is_match(uri)
+    /// Whether the uri carries a response of the provider, either a code or an error.
+    pub fn uri_includes_response(uri: &str) -> bool {
+        Url::parse(uri).map_or(false, |url| {
+            url.query_pairs()
+                .any(|(key, _)| key == "code" || key == "error")
+        })
     }
 }
 
@@ -91,6 +88,11 @@ pub struct TokenDisplayClaims {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserHash {
     uhs: String,
+    /// Only sent when authorizing for `http://xboxlive.com`.
+    #[serde(default)]
+    xid: Option<String>,
+    #[serde(default)]
+    gtg: Option<String>,
 }
 
 impl Into<String> for UserHash {
@@ -111,7 +113,24 @@ pub struct XSTSToken {
     display_claims: TokenDisplayClaims,
 }
 
+/// Xbox user details read from the display claims of a XSTS token.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct XboxClaims {
+    pub xuid: Option<String>,
+    pub gamertag: Option<String>,
+}
+
 impl XSTSToken {
+    pub fn claims(&self) -> XboxClaims {
+        match self.display_claims.xui.first() {
+            Some(user) => XboxClaims {
+                xuid: user.xid.clone(),
+                gamertag: user.gtg.clone(),
+            },
+            None => XboxClaims::default(),
+        }
+    }
+
     /// RETURNS (uhs, token)
     pub fn extract_tokens(self) -> Option<(String, String)> {
         if let Some(token) = self.display_claims.xui.first() {
@@ -130,8 +149,109 @@ pub struct MinecraftToken {
     pub expires_in: u64,
 }
 
+impl MinecraftToken {
+    /// Token handed to the game for offline accounts, servers in offline mode don't check it.
+    pub fn placeholder(username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            access_token: "0".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: u64::MAX,
+        }
+    }
+
+    /// Token issued by a Yggdrasil server, which doesn't tell when it expires
+    /// so it is validated against the server before being used.
+    pub fn bearer(username: &str, access_token: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            access_token: access_token.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: u64::MAX,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MinecraftProfile {
     pub id: String,
     pub name: String,
+    #[serde(default)]
+    pub skins: Vec<ProfileSkin>,
+    #[serde(default)]
+    pub capes: Vec<ProfileCape>,
+}
+
+impl MinecraftProfile {
+    pub fn active_skin(&self) -> Option<&ProfileSkin> {
+        self.skins
+            .iter()
+            .find(|skin| skin.state == TextureState::ACTIVE)
+    }
+
+    pub fn active_cape(&self) -> Option<&ProfileCape> {
+        self.capes
+            .iter()
+            .find(|cape| cape.state == TextureState::ACTIVE)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureState {
+    ACTIVE,
+    INACTIVE,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkinVariant {
+    CLASSIC,
+    SLIM,
+}
+
+impl SkinVariant {
+    /// Value expected by the skin endpoints.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkinVariant::CLASSIC => "classic",
+            SkinVariant::SLIM => "slim",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileSkin {
+    pub id: String,
+    pub state: TextureState,
+    pub url: String,
+    pub variant: SkinVariant,
+    pub alias: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileCape {
+    pub id: String,
+    pub state: TextureState,
+    pub url: String,
+    pub alias: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NameChangeEligibility {
+    pub changed_at: Option<String>,
+    pub created_at: Option<String>,
+    pub name_change_allowed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NameAvailability {
+    Available,
+    Duplicate,
+    NotAllowed,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NameAvailabilityResponse {
+    pub status: NameAvailability,
 }
@@ -0,0 +1,513 @@
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::auth_route::accounts::{Account, AccountKind};
+use crate::auth_route::errors::AuthError;
+use crate::auth_route::tokens::{MinecraftProfile, MinecraftToken};
+use crate::auth_route::utils;
+
+/// Header used by authlib-injector servers to point at their API root.
+const API_LOCATION_HEADER: &str = "x-authlib-injector-api-location";
+
+/// A Yggdrasil compatible authentication server an account belongs to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct YggdrasilServer {
+    /// Root of the API, the one authlib-injector is pointed at.
+    pub url: String,
+    /// Name advertised in the server metadata.
+    pub name: Option<String>,
+    /// Sessions are bound to the client token they were created with.
+    pub client_token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct YggdrasilProfile {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YggdrasilSession {
+    access_token: String,
+    client_token: String,
+    #[serde(default)]
+    available_profiles: Vec<YggdrasilProfile>,
+    selected_profile: Option<YggdrasilProfile>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YggdrasilErrorResponse {
+    error: String,
+    error_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ServerMeta {
+    server_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ServerMetadata {
+    #[serde(default)]
+    meta: ServerMeta,
+}
+
+/// Client for the `authserver` endpoints of a Yggdrasil server.
+pub struct YggdrasilClient {
+    client: Client,
+    url: Url,
+}
+
+impl YggdrasilClient {
+    pub fn new(url: &str) -> Result<Self, AuthError> {
+        // Endpoints are joined to the root, which only works with a trailing slash.
+        let url = match url.ends_with('/') {
+            true => url.to_string(),
+            false => format!("{}/", url),
+        };
+
+        let url = Url::parse(&url).map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        Ok(Self {
+            client: Client::new(),
+            url,
+        })
+    }
+
+    /// Follows the API location indication of the server, so users can type
+    /// the address of its website instead of the API root.
+    pub async fn resolve(url: &str) -> Result<Self, AuthError> {
+        let client = Self::new(url)?;
+
+        let response = client
+            .client
+            .get(client.url.clone())
+            .send()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        let location = response
+            .headers()
+            .get(API_LOCATION_HEADER)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| response.url().join(location).ok());
+
+        match location {
+            Some(location) if location != client.url => Self::new(location.as_str()),
+            _ => Ok(client),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Raw metadata of the server, handed as is to authlib-injector.
+    pub async fn metadata(&self) -> Result<String, AuthError> {
+        let response = self
+            .client
+            .get(self.url.clone())
+            .send()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        let metadata = Self::extract_response(response)
+            .await?
+            .text()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        serde_json::from_str::<Value>(&metadata)
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        Ok(metadata)
+    }
+
+    async fn server_name(&self) -> Result<Option<String>, AuthError> {
+        let metadata = self.metadata().await?;
+        let metadata = serde_json::from_str::<ServerMetadata>(&metadata).unwrap_or_default();
+
+        Ok(metadata.meta.server_name)
+    }
+
+    async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+        client_token: &str,
+    ) -> Result<YggdrasilSession, AuthError> {
+        let body = json!({
+            "agent": {
+                "name": "Minecraft",
+                "version": 1
+            },
+            "username": username,
+            "password": password,
+            "clientToken": client_token,
+            "requestUser": false
+        });
+
+        self.post_json("authserver/authenticate", body).await
+    }
+
+    async fn refresh(
+        &self,
+        access_token: &str,
+        client_token: &str,
+        profile: Option<&YggdrasilProfile>,
+    ) -> Result<YggdrasilSession, AuthError> {
+        let mut body = json!({
+            "accessToken": access_token,
+            "clientToken": client_token,
+            "requestUser": false
+        });
+
+        if let Some(profile) = profile {
+            body["selectedProfile"] = json!(profile);
+        }
+
+        let response = self.send("authserver/refresh", body).await?;
+
+        // Sessions which can no longer be refreshed are answered with 403.
+        if response.status() == StatusCode::FORBIDDEN {
+            return match Self::extract_response(response).await {
+                Err(AuthError::YggdrasilError(message)) => Err(AuthError::SessionRejected(message)),
+                _ => Err(AuthError::SessionRejected(StatusCode::FORBIDDEN.to_string())),
+            };
+        }
+
+        Self::extract_response(response)
+            .await?
+            .json::<YggdrasilSession>()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))
+    }
+
+    /// Whether the access token can still be used to join servers.
+    pub async fn validate(&self, access_token: &str, client_token: &str) -> Result<bool, AuthError> {
+        let body = json!({
+            "accessToken": access_token,
+            "clientToken": client_token
+        });
+
+        let response = self.send("authserver/validate", body).await?;
+
+        // Invalid tokens are answered with 403 and an error payload.
+        match response.status() {
+            StatusCode::FORBIDDEN => Ok(false),
+            _ => Self::extract_response(response).await.map(|_| true),
+        }
+    }
+
+    pub async fn invalidate(&self, access_token: &str, client_token: &str) -> Result<(), AuthError> {
+        let body = json!({
+            "accessToken": access_token,
+            "clientToken": client_token
+        });
+
+        self.post("authserver/invalidate", body).await.map(|_| ())
+    }
+
+    /// Invalidates every session of the user, not only the ones of this launcher.
+    pub async fn signout(&self, username: &str, password: &str) -> Result<(), AuthError> {
+        let body = json!({
+            "username": username,
+            "password": password
+        });
+
+        self.post("authserver/signout", body).await.map(|_| ())
+    }
+
+    async fn post_json(&self, path: &str, body: Value) -> Result<YggdrasilSession, AuthError> {
+        self.post(path, body)
+            .await?
+            .json::<YggdrasilSession>()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<Response, AuthError> {
+        let response = self.send(path, body).await?;
+        Self::extract_response(response).await
+    }
+
+    async fn send(&self, path: &str, body: Value) -> Result<Response, AuthError> {
+        let url = self
+            .url
+            .join(path)
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        self.client
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))
+    }
+
+    async fn extract_response(response: Response) -> Result<Response, AuthError> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let err = response
+            .text()
+            .await
+            .map_err(|err| AuthError::YggdrasilError(err.to_string()))?;
+
+        let message = match serde_json::from_str::<YggdrasilErrorResponse>(&err) {
+            Ok(YggdrasilErrorResponse {
+                error_message: Some(message),
+                ..
+            }) => message,
+            Ok(YggdrasilErrorResponse { error, .. }) => error,
+            Err(_) => format!("Status: {}, Body: {}", status, err),
+        };
+
+        Err(AuthError::YggdrasilError(message))
+    }
+}
+
+/// Signs in against the server and returns an account for the selected profile.
+pub async fn create_account(server: &str, username: &str, password: &str) -> Result<Account, AuthError> {
+    let client = YggdrasilClient::resolve(server).await?;
+    let name = client.server_name().await?;
+
+    let mut session = client
+        .authenticate(username, password, &utils::next_state())
+        .await?;
+
+    // Users owning several profiles have to pick one, we bind the session to the first.
+    if session.selected_profile.is_none() {
+        if let Some(profile) = session.available_profiles.first().cloned() {
+            session = client
+                .refresh(&session.access_token, &session.client_token, Some(&profile))
+                .await?;
+        }
+    }
+
+    let profile = session
+        .selected_profile
+        .ok_or(AuthError::MissingMinecraftProfile)?;
+
+    let mut account = Account::new(
+        AccountKind::Yggdrasil,
+        MinecraftProfile {
+            id: profile.id,
+            name: profile.name.clone(),
+            skins: vec![],
+            capes: vec![],
+        },
+        None,
+        MinecraftToken::bearer(&profile.name, &session.access_token),
+        u64::MAX,
+    );
+
+    account.server = Some(YggdrasilServer {
+        url: client.url().to_string(),
+        name,
+        client_token: session.client_token,
+    });
+
+    Ok(account)
+}
+
+/// Invalidates the access token of the account.
+pub async fn invalidate_account(account: &Account) -> Result<(), AuthError> {
+    let server = account
+        .server
+        .as_ref()
+        .ok_or_else(|| AuthError::YggdrasilError("Missing authentication server".into()))?;
+
+    YggdrasilClient::new(&server.url)?
+        .invalidate(&account.mc.access_token, &server.client_token)
+        .await
+}
+
+/// Refreshes the access token of the account if the server no longer accepts it.
+/// Returns whether the account has been updated.
+pub async fn validate_account(account: &mut Account) -> Result<bool, AuthError> {
+    let server = account
+        .server
+        .clone()
+        .ok_or_else(|| AuthError::YggdrasilError("Missing authentication server".into()))?;
+
+    let client = YggdrasilClient::new(&server.url)?;
+
+    if client
+        .validate(&account.mc.access_token, &server.client_token)
+        .await?
+    {
+        return Ok(false);
+    }
+
+    let profile = YggdrasilProfile {
+        id: account.profile.id.clone(),
+        name: account.profile.name.clone(),
+    };
+
+    let session = client
+        .refresh(&account.mc.access_token, &server.client_token, Some(&profile))
+        .await?;
+
+    if let Some(profile) = session.selected_profile {
+        account.profile.name = profile.name;
+    }
+
+    account.mc = MinecraftToken::bearer(&account.profile.name, &session.access_token);
+    account.needs_login = false;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{json, TestServer};
+
+    const CLIENT_TOKEN: &str = "client";
+
+    fn account(server: &TestServer) -> Account {
+        let mut account = Account::new(
+            AccountKind::Yggdrasil,
+            MinecraftProfile {
+                id: "0a1b".to_string(),
+                name: "Steve".to_string(),
+                skins: vec![],
+                capes: vec![],
+            },
+            None,
+            MinecraftToken::bearer("Steve", "old"),
+            u64::MAX,
+        );
+
+        account.server = Some(YggdrasilServer {
+            url: server.url.clone(),
+            name: None,
+            client_token: CLIENT_TOKEN.to_string(),
+        });
+
+        account
+    }
+
+    fn invalid_token() -> (u16, Vec<u8>) {
+        json(
+            403,
+            json!({ "error": "ForbiddenOperationException", "errorMessage": "Invalid token." }),
+        )
+    }
+
+    #[tokio::test]
+    async fn keeps_valid_sessions() {
+        let server = TestServer::start(|_| (204, vec![])).await;
+        let mut account = account(&server);
+
+        assert!(!validate_account(&mut account).await.unwrap());
+        assert_eq!(account.mc.access_token, "old");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/authserver/validate");
+        assert_eq!(requests[0].json()["clientToken"], CLIENT_TOKEN);
+    }
+
+    #[tokio::test]
+    async fn refreshes_invalid_sessions() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/authserver/validate" => invalid_token(),
+            _ => json(
+                200,
+                json!({
+                    "accessToken": "new",
+                    "clientToken": CLIENT_TOKEN,
+                    "selectedProfile": { "id": "0a1b", "name": "Alex" }
+                }),
+            ),
+        })
+        .await;
+        let mut account = account(&server);
+        account.needs_login = true;
+
+        assert!(validate_account(&mut account).await.unwrap());
+        assert_eq!(account.mc.access_token, "new");
+        assert_eq!(account.profile.name, "Alex");
+        assert!(!account.needs_login);
+
+        let refresh = &server.requests()[1];
+        assert_eq!(refresh.path, "/authserver/refresh");
+        assert_eq!(refresh.json()["accessToken"], "old");
+        assert_eq!(refresh.json()["selectedProfile"]["id"], "0a1b");
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_sessions() {
+        let server = TestServer::start(|_| invalid_token()).await;
+        let mut account = account(&server);
+
+        let err = validate_account(&mut account).await.unwrap_err();
+
+        assert!(err.is_rejection());
+        assert!(matches!(err, AuthError::SessionRejected(message) if message == "Invalid token."));
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_not_rejections() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/authserver/validate" => invalid_token(),
+            _ => (503, vec![]),
+        })
+        .await;
+        let mut account = account(&server);
+
+        let err = validate_account(&mut account).await.unwrap_err();
+
+        assert!(!err.is_rejection());
+        assert_eq!(account.mc.access_token, "old");
+    }
+
+    #[tokio::test]
+    async fn binds_new_sessions_to_the_first_profile() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/" => json(200, json!({ "meta": { "serverName": "Test Server" } })),
+            "/authserver/authenticate" => json(
+                200,
+                json!({
+                    "accessToken": "unbound",
+                    "clientToken": CLIENT_TOKEN,
+                    "availableProfiles": [
+                        { "id": "0a1b", "name": "Steve" },
+                        { "id": "2c3d", "name": "Alex" }
+                    ]
+                }),
+            ),
+            _ => json(
+                200,
+                json!({
+                    "accessToken": "bound",
+                    "clientToken": CLIENT_TOKEN,
+                    "selectedProfile": request.json()["selectedProfile"]
+                }),
+            ),
+        })
+        .await;
+
+        let account = create_account(&server.url, "steve@example.com", "password")
+            .await
+            .unwrap();
+
+        assert_eq!(account.kind, AccountKind::Yggdrasil);
+        assert_eq!(account.profile.id, "0a1b");
+        assert_eq!(account.mc.access_token, "bound");
+
+        let server_info = account.server.unwrap();
+        assert_eq!(server_info.url, format!("{}/", server.url));
+        assert_eq!(server_info.name.as_deref(), Some("Test Server"));
+        assert_eq!(server_info.client_token, CLIENT_TOKEN);
+    }
+}
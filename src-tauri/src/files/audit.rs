@@ -0,0 +1,64 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::files::errors::FileError;
+
+const AUDIT_FILE: &str = "audit.log";
+
+#[derive(Serialize, Clone, Debug)]
+pub enum AuditAction {
+    SIGNOUT,
+}
+
+/// Entry of the audit log, which must never hold token material.
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub account: String,
+    pub name: String,
+    /// Whether the session was invalidated with the service which issued it.
+    pub invalidated: bool,
+    /// Whether the OAuth refresh token was forgotten along with the account.
+    pub forgot_refresh_token: bool,
+    /// Whether the logout pages of the login domains were requested.
+    /// It is all we can tell, the webview doesn't report whether its cookies were cleared.
+    pub logout_requested: bool,
+}
+
+/// Append only log of security relevant actions, one JSON entry per line.
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(base: &PathBuf) -> AuditLog {
+        AuditLog {
+            path: base.join("logs").join(AUDIT_FILE),
+        }
+    }
+
+    pub fn ensure_exists(&self) -> std::io::Result<()> {
+        match self.path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record(&self, entry: &AuditEntry) -> Result<(), FileError> {
+        let mut line = serde_json::to_vec(entry)
+            .map_err(|err| FileError::CouldNotSaveFile(err.into()))?;
+        line.push(b'\n');
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| FileError::CouldNotSaveFile(err.into()))?;
+
+        file.write_all(&line)
+            .map_err(|err| FileError::CouldNotSaveFile(err.into()))
+    }
+}
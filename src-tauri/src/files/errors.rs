@@ -9,4 +9,10 @@ pub enum FileError {
     #[error("Could not save file: {0}")]
     CouldNotSaveFile(anyhow::Error),
 
+    #[error("Could not access secret: {0}")]
+    CouldNotAccessSecret(anyhow::Error),
+
+    #[error("Invalid settings: {0}")]
+    InvalidSettings(String),
+
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::files::errors::FileError;
+use crate::files::settings::{ConfigurationFile, Settings};
+
+pub const INSTANCE_SETTINGS_FILE: &str = "instance.json";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// JVM and game settings of an instance, stored in `instances/<id>/instance.json`.
+/// Fields left empty fall back to the defaults of the launcher settings.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InstanceSettings {
+    /// Initial heap size, in megabytes.
+    #[serde(default)]
+    pub min_memory: Option<u32>,
+    /// Maximum heap size, in megabytes.
+    #[serde(default)]
+    pub max_memory: Option<u32>,
+    /// Passed to the JVM after the ones of the version.
+    #[serde(default)]
+    pub jvm_arguments: Option<Vec<String>>,
+    #[serde(default)]
+    pub resolution: Option<Resolution>,
+    #[serde(default)]
+    pub fullscreen: Option<bool>,
+    /// Added to the environment of the game, overriding the defaults with the same name.
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    /// Command the game is started through, like `gamemoderun` or `prime-run`.
+    #[serde(default)]
+    pub wrapper: Option<String>,
+}
+
+impl InstanceSettings {
+    pub fn open(instance: &PathBuf) -> Result<ConfigurationFile<Self>, FileError> {
+        Settings::setup_file(instance, INSTANCE_SETTINGS_FILE)
+    }
+
+    /// Fills the fields left empty with the ones of `defaults`.
+    pub fn merged(&self, defaults: &InstanceSettings) -> InstanceSettings {
+        let mut environment = defaults.environment.clone();
+        environment.extend(self.environment.clone());
+
+        InstanceSettings {
+            min_memory: self.min_memory.or(defaults.min_memory),
+            max_memory: self.max_memory.or(defaults.max_memory),
+            jvm_arguments: self
+                .jvm_arguments
+                .clone()
+                .or_else(|| defaults.jvm_arguments.clone()),
+            resolution: self.resolution.or(defaults.resolution),
+            fullscreen: self.fullscreen.or(defaults.fullscreen),
+            environment,
+            wrapper: self.wrapper.clone().or_else(|| defaults.wrapper.clone()),
+        }
+    }
+
+    /// Copy without the fields able to run commands on the machine, for settings
+    /// coming from an archive we can't trust.
+    pub fn without_commands(&self) -> InstanceSettings {
+        InstanceSettings {
+            jvm_arguments: None,
+            environment: HashMap::new(),
+            wrapper: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), FileError> {
+        if let (Some(min), Some(max)) = (self.min_memory, self.max_memory) {
+            if min > max {
+                return Err(FileError::InvalidSettings(format!(
+                    "minimum memory ({}M) is above the maximum ({}M)",
+                    min, max
+                )));
+            }
+        }
+
+        if let Some(resolution) = self.resolution {
+            if resolution.width == 0 || resolution.height == 0 {
+                return Err(FileError::InvalidSettings(
+                    "resolution can't be empty".to_string(),
+                ));
+            }
+        }
+
+        if let Some(wrapper) = &self.wrapper {
+            if wrapper.trim().is_empty() {
+                return Err(FileError::InvalidSettings(
+                    "wrapper command can't be empty".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Program and leading arguments the java command is prefixed with.
+    pub fn wrapper_command(&self) -> Vec<String> {
+        self.wrapper
+            .as_ref()
+            .map(|wrapper| wrapper.split_whitespace().map(String::from).collect())
+            .unwrap_or_default()
+    }
+}
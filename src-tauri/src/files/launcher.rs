@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use crate::files::instance::InstanceSettings;
+use crate::locale::Language;
+
+/// Launcher wide preferences, stored in `launcher.json`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LauncherSettings {
+    /// Allows creating and playing with offline (non-premium) accounts.
+    /// Meant for development, so it is only enabled by default on debug builds.
+    #[serde(default = "default_allow_offline_accounts")]
+    pub allow_offline_accounts: bool,
+    /// Seconds the login window waits for the user before giving up.
+    #[serde(default = "default_login_timeout")]
+    pub login_timeout: u64,
+    /// Refuses to launch when a version uses an argument placeholder we don't know.
+    #[serde(default)]
+    pub strict_arguments: bool,
+    /// Language of the texts shown by the backend, like the login window.
+    #[serde(default)]
+    pub language: Language,
+    /// Used by the instances for the settings they leave empty.
+    #[serde(default)]
+    pub instance_defaults: InstanceSettings,
+    /// Base url of the CurseForge API, which can point to a proxy holding the key.
+    #[serde(default = "default_curseforge_api_url")]
+    pub curseforge_api_url: String,
+    #[serde(default, with = "crate::files::secrets::sealed")]
+    pub curseforge_api_key: Option<String>,
+}
+
+fn default_allow_offline_accounts() -> bool {
+    cfg!(debug_assertions)
+}
+
+fn default_login_timeout() -> u64 {
+    5 * 60
+}
+
+fn default_curseforge_api_url() -> String {
+    "https://api.curseforge.com".to_string()
+}
+
+impl Default for LauncherSettings {
+    fn default() -> Self {
+        Self {
+            allow_offline_accounts: default_allow_offline_accounts(),
+            login_timeout: default_login_timeout(),
+            strict_arguments: false,
+            language: Language::default(),
+            instance_defaults: InstanceSettings::default(),
+            curseforge_api_url: default_curseforge_api_url(),
+            curseforge_api_key: None,
+        }
+    }
+}
@@ -1,4 +1,8 @@
 mod errors;
 
+pub mod audit;
+pub mod instance;
+pub mod launcher;
 pub mod storage;
-pub mod settings;
\ No newline at end of file
+pub mod settings;
+pub mod secrets;
\ No newline at end of file
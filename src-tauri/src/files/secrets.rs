@@ -0,0 +1,274 @@
+use std::fs;
+use std::io::Write;
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::Arc;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::anyhow;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use crate::files::errors::FileError;
+
+const SECRET_FILE: &str = "secret.key";
+const SECRET_LENGTH: usize = 64;
+const NONCE_LENGTH: usize = 12;
+const KEY_CONTEXT: &[u8] = b"cognatize/accounts/v1";
+
+/// Every sealed value starts with this prefix, anything else found in a
+/// sealed field is considered legacy plaintext.
+const SEALED_PREFIX: &str = "sealed:v1:";
+
+thread_local! {
+    /// Store of the file currently read or written on this thread, see `scoped`.
+    static SCOPE: RefCell<Option<Scope>> = RefCell::new(None);
+}
+
+/// Encrypts token material before it reaches the settings files.
+///
+/// The key is derived from a random secret kept next to the settings folder,
+/// readable only by the current user. This works the same on desktops and on
+/// headless machines since it doesn't depend on any OS keyring.
+pub struct SecretStore {
+    cipher: Aes256Gcm,
+}
+
+impl SecretStore {
+
+    /// Loads (or creates) the secret inside `base`.
+    pub fn load(base: &Path) -> Result<Arc<Self>, FileError> {
+        Self::load_or_create(&base.join(SECRET_FILE)).map(Arc::new)
+    }
+
+    fn load_or_create(path: &Path) -> Result<Self, FileError> {
+        let secret = match path.try_exists()
+            .map_err(|err| FileError::CouldNotAccessSecret(err.into()))? {
+            true => {
+                restrict_permissions(path)?;
+                fs::read(path)
+                    .map_err(|err| FileError::CouldNotAccessSecret(err.into()))?
+            }
+            false => {
+                info!("Creating secret file: {}", path.display());
+                Self::create_secret(path)?
+            }
+        };
+
+        if secret.len() < SECRET_LENGTH {
+            return Err(FileError::CouldNotAccessSecret(anyhow!("Secret file is too short")));
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(KEY_CONTEXT);
+        hasher.update(&secret);
+        let key = hasher.finalize();
+
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|err| FileError::CouldNotAccessSecret(anyhow!("{}", err)))?;
+
+        Ok(Self {
+            cipher,
+        })
+    }
+
+    fn create_secret(path: &Path) -> Result<Vec<u8>, FileError> {
+        let mut secret = vec![0u8; SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(path)
+            .map_err(|err| FileError::CouldNotAccessSecret(err.into()))?;
+
+        file.write_all(&secret)
+            .map_err(|err| FileError::CouldNotAccessSecret(err.into()))?;
+
+        Ok(secret)
+    }
+
+    pub fn seal(&self, plain: &[u8]) -> Result<String, FileError> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let encrypted = self.cipher.encrypt(Nonce::from_slice(&nonce), plain)
+            .map_err(|err| FileError::CouldNotAccessSecret(anyhow!("{}", err)))?;
+
+        let mut blob = nonce.to_vec();
+        blob.extend(encrypted);
+
+        Ok(format!("{}{}", SEALED_PREFIX, STANDARD.encode(blob)))
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<Vec<u8>, FileError> {
+        let encoded = sealed.strip_prefix(SEALED_PREFIX)
+            .ok_or_else(|| FileError::CouldNotAccessSecret(anyhow!("Value is not sealed")))?;
+
+        let blob = STANDARD.decode(encoded)
+            .map_err(|err| FileError::CouldNotAccessSecret(err.into()))?;
+
+        if blob.len() < NONCE_LENGTH {
+            return Err(FileError::CouldNotAccessSecret(anyhow!("Sealed value is truncated")));
+        }
+
+        let (nonce, encrypted) = blob.split_at(NONCE_LENGTH);
+
+        self.cipher.decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|err| FileError::CouldNotAccessSecret(anyhow!("{}", err)))
+    }
+
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<(), FileError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let permissions = fs::metadata(path)
+        .map_err(|err| FileError::CouldNotAccessSecret(err.into()))?
+        .permissions();
+
+    if permissions.mode() & 0o077 != 0 {
+        warn!("Secret file was readable by other users, restricting it");
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .map_err(|err| FileError::CouldNotAccessSecret(err.into()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> Result<(), FileError> {
+    Ok(())
+}
+
+struct Scope {
+    store: Arc<SecretStore>,
+    migrated: bool,
+}
+
+/// Puts back the previous scope, even when the closure panics.
+struct ScopeGuard(Option<Option<Scope>>);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            SCOPE.with(|scope| scope.replace(previous));
+        }
+    }
+}
+
+/// Runs `f` with `store` sealing and opening the sealed fields it serializes
+/// or deserializes. Also returns whether plaintext values were read, meaning
+/// the file they came from should be saved again to seal them.
+pub fn scoped<R>(store: &Arc<SecretStore>, f: impl FnOnce() -> R) -> (R, bool) {
+    let previous = SCOPE.with(|scope| scope.replace(Some(Scope {
+        store: store.clone(),
+        migrated: false,
+    })));
+    let mut guard = ScopeGuard(Some(previous));
+
+    let result = f();
+
+    let current = guard.0.take()
+        .and_then(|previous| SCOPE.with(|scope| scope.replace(previous)));
+
+    (result, current.map_or(false, |scope| scope.migrated))
+}
+
+/// Serde adapter used as `#[serde(with = "crate::files::secrets::sealed")]` on
+/// fields holding token material. The field is written as a sealed string and
+/// plaintext values from older files are still accepted when reading.
+///
+/// Outside of `scoped`, e.g. when sent to the frontend, the field is written
+/// as null so token material never leaves the backend.
+pub mod sealed {
+    use serde::de::{DeserializeOwned, Error as DeError};
+    use serde::ser::Error as SerError;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+    use super::{SCOPE, SEALED_PREFIX};
+
+    pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            T: Serialize
+    {
+        let sealed = SCOPE.with(|scope| match &*scope.borrow() {
+            Some(scope) => {
+                let plain = serde_json::to_vec(value).map_err(S::Error::custom)?;
+                scope.store.seal(&plain).map(Some).map_err(S::Error::custom)
+            }
+            None => Ok(None),
+        })?;
+
+        match sealed {
+            Some(sealed) => serializer.serialize_str(&sealed),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: DeserializeOwned
+    {
+        let value = Value::deserialize(deserializer)?;
+
+        SCOPE.with(|scope| {
+            let mut scope = scope.borrow_mut();
+            let scope = scope.as_mut()
+                .ok_or_else(|| D::Error::custom("Sealed value read outside of a secret scope"))?;
+
+            match value {
+                Value::String(sealed) if sealed.starts_with(SEALED_PREFIX) => {
+                    let plain = scope.store.open(&sealed).map_err(D::Error::custom)?;
+                    serde_json::from_slice(&plain).map_err(D::Error::custom)
+                }
+                value => {
+                    scope.migrated = true;
+                    serde_json::from_value(value).map_err(D::Error::custom)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Holder {
+        #[serde(with = "sealed")]
+        token: String,
+    }
+
+    #[test]
+    fn only_seals_within_a_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SecretStore::load(dir.path()).unwrap();
+        let holder = Holder { token: "token".to_string() };
+
+        let (sealed, _) = scoped(&store, || serde_json::to_string(&holder).unwrap());
+        assert!(sealed.contains(SEALED_PREFIX));
+        assert_eq!(serde_json::to_string(&holder).unwrap(), r#"{"token":null}"#);
+
+        let (opened, migrated) = scoped(&store, || serde_json::from_str::<Holder>(&sealed).unwrap());
+        assert_eq!(opened.token, "token");
+        assert!(!migrated);
+
+        let (_, migrated) = scoped(&store, || serde_json::from_str::<Holder>(r#"{"token":"token"}"#).unwrap());
+        assert!(migrated);
+        assert!(serde_json::from_str::<Holder>(&sealed).is_err());
+    }
+}
@@ -2,20 +2,27 @@ use std::path::{Path, PathBuf};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use crate::files::errors::{FileError};
+use crate::files::launcher::LauncherSettings;
+use crate::files::secrets::{self, SecretStore};
 use std::fs;
+use std::sync::Arc;
 use tracing::info;
 use crate::auth_route::accounts::AccountStorage;
 use crate::version_manager::games::{GameStorage};
+use crate::version_manager::instances::InstanceStorage;
 
 const SETTINGS_FOLDER: &str = "settings";
 
 pub struct Settings {
     pub accounts: ConfigurationFile<AccountStorage>,
-    pub games: ConfigurationFile<GameStorage>
+    pub games: ConfigurationFile<GameStorage>,
+    pub instances: ConfigurationFile<InstanceStorage>,
+    pub launcher: ConfigurationFile<LauncherSettings>,
 }
 
 pub struct ConfigurationFile<T: Serialize + DeserializeOwned> {
     path: PathBuf,
+    secrets: Option<Arc<SecretStore>>,
     pub contents: T,
 }
 
@@ -27,7 +34,10 @@ impl<T: Serialize + DeserializeOwned> ConfigurationFile<T> {
                 .map_err(|err| FileError::CouldNotSaveFile(err.into()))?;
         }
 
-        let contents = serde_json::to_vec(&self.contents)
+        let contents = match &self.secrets {
+            Some(store) => secrets::scoped(store, || serde_json::to_vec(&self.contents)).0,
+            None => serde_json::to_vec(&self.contents),
+        }
             .map_err(|err| FileError::CouldNotSaveFile(err.into()))?;
 
         fs::write(&self.path, contents)
@@ -40,28 +50,68 @@ impl<T: Serialize + DeserializeOwned> ConfigurationFile<T> {
 impl Settings {
 
     pub fn new(base: &PathBuf) -> Result<Self, FileError> {
+        let secrets = SecretStore::load(base)?;
+
         let base = base.join(SETTINGS_FOLDER);
 
-        let accounts = Self::setup_file(&base, "accounts.json")?;
+        let mut accounts = Self::setup_sealed_file::<AccountStorage>(&base, "accounts.json", &secrets)?;
+
+        if accounts.contents.migrate_legacy() {
+            info!("Moved legacy OAuth tokens of accounts.json into identities");
+            accounts.save()?;
+        }
+
         let games = Self::setup_file(&base, "games.json")?;
+        let launcher = Self::setup_sealed_file::<LauncherSettings>(&base, "launcher.json", &secrets)?;
+
+        let mut instances = Self::setup_file::<InstanceStorage>(&base, "instances.json")?;
+
+        if instances.contents.ensure_games() {
+            info!("Added the instances of the games to instances.json");
+            instances.save()?;
+        }
 
         Ok(Self {
             accounts,
-            games
+            games,
+            instances,
+            launcher,
         })
     }
 
-    fn setup_file<T>(base: &PathBuf, path: impl AsRef<Path>) -> Result<ConfigurationFile<T>, FileError>
+    pub fn setup_file<T>(base: &PathBuf, path: impl AsRef<Path>) -> Result<ConfigurationFile<T>, FileError>
+        where
+            T: Serialize + DeserializeOwned + Default
+    {
+        Self::setup(base, path, None)
+    }
+
+    /// Like `setup_file`, for files holding sealed fields. Files written before
+    /// their tokens were sealed are saved again right away to seal them.
+    pub fn setup_sealed_file<T>(base: &PathBuf, path: impl AsRef<Path>, secrets: &Arc<SecretStore>) -> Result<ConfigurationFile<T>, FileError>
+        where
+            T: Serialize + DeserializeOwned + Default
+    {
+        Self::setup(base, path, Some(secrets.clone()))
+    }
+
+    fn setup<T>(base: &PathBuf, path: impl AsRef<Path>, secrets: Option<Arc<SecretStore>>) -> Result<ConfigurationFile<T>, FileError>
         where
             T: Serialize + DeserializeOwned + Default
     {
         info!("Setting up file: {}", path.as_ref().display());
-        let possible_file = Self::load_file(base, &path)?;
+        let possible_file = Self::load_file(base, &path, secrets.clone())?;
 
         let file = match possible_file {
-            Some(file) => file,
+            Some((file, migrated)) => {
+                if migrated {
+                    info!("Sealing plaintext secrets found in {}", path.as_ref().display());
+                    file.save()?;
+                }
+                file
+            }
             None => {
-                let file = Self::create_file(base, path, T::default());
+                let file = Self::create_file(base, path, secrets, T::default());
                 file.save()?;
                 file
             }
@@ -70,14 +120,16 @@ impl Settings {
         Ok(file)
     }
 
-    fn create_file<T: Serialize + DeserializeOwned>(base: &PathBuf, path: impl AsRef<Path>, contents: T) -> ConfigurationFile<T> {
+    fn create_file<T: Serialize + DeserializeOwned>(base: &PathBuf, path: impl AsRef<Path>, secrets: Option<Arc<SecretStore>>, contents: T) -> ConfigurationFile<T> {
         ConfigurationFile {
             path: base.join(path),
+            secrets,
             contents,
         }
     }
 
-    fn load_file<T: Serialize + DeserializeOwned>(base: &PathBuf, path: impl AsRef<Path>) -> Result<Option<ConfigurationFile<T>>, FileError> {
+    /// Also returns whether sealed fields were read as plaintext.
+    fn load_file<T: Serialize + DeserializeOwned>(base: &PathBuf, path: impl AsRef<Path>, secrets: Option<Arc<SecretStore>>) -> Result<Option<(ConfigurationFile<T>, bool)>, FileError> {
         let path = base.join(path);
 
         let exists = path.try_exists()
@@ -90,13 +142,74 @@ impl Settings {
         let bytes = fs::read(&path)
             .map_err(|err| FileError::CouldNotLoadFile(err.into()))?;
 
-        let contents = serde_json::from_slice(&bytes)
+        let (contents, migrated) = match &secrets {
+            Some(store) => secrets::scoped(store, || serde_json::from_slice(&bytes)),
+            None => (serde_json::from_slice(&bytes), false),
+        };
+        let contents = contents
             .map_err(|err| FileError::CouldNotLoadFile(err.into()))?;
 
-        Ok(Some(ConfigurationFile {
+        Ok(Some((ConfigurationFile {
             path,
+            secrets,
             contents,
-        }))
+        }, migrated)))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seals_the_plaintext_curseforge_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let launcher = dir.path().join(SETTINGS_FOLDER).join("launcher.json");
+        fs::create_dir_all(launcher.parent().unwrap()).unwrap();
+        fs::write(&launcher, r#"{"curseforge_api_key": "plaintext-key"}"#).unwrap();
+
+        let settings = Settings::new(&dir.path().to_path_buf()).unwrap();
+        let stored = fs::read_to_string(&launcher).unwrap();
+
+        assert_eq!(settings.launcher.contents.curseforge_api_key.as_deref(), Some("plaintext-key"));
+        assert!(!stored.contains("plaintext-key"));
+        assert!(stored.contains(r#""curseforge_api_key":"sealed:v1:"#));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn keeps_the_secrets_of_each_base_apart() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+
+        let mut settings = Settings::new(&first.path().to_path_buf()).unwrap();
+        settings.launcher.contents.curseforge_api_key = Some("first-key".to_string());
+        settings.launcher.save().unwrap();
+
+        let other = Settings::new(&second.path().to_path_buf()).unwrap();
+
+        assert!(second.path().join("secret.key").exists());
+        assert_ne!(fs::read(first.path().join("secret.key")).unwrap(),
+                   fs::read(second.path().join("secret.key")).unwrap());
+        assert_eq!(other.launcher.contents.curseforge_api_key, None);
+
+        let reloaded = Settings::new(&first.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.launcher.contents.curseforge_api_key.as_deref(), Some("first-key"));
+    }
+
+    #[test]
+    fn only_saves_the_files_holding_plaintext() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join(SETTINGS_FOLDER);
+        fs::create_dir_all(&folder).unwrap();
+
+        let accounts = r#"{"accounts": [], "elected_account": null}"#;
+        fs::write(folder.join("accounts.json"), accounts).unwrap();
+        fs::write(folder.join("launcher.json"), r#"{"curseforge_api_key": "plaintext-key"}"#).unwrap();
+
+        Settings::new(&dir.path().to_path_buf()).unwrap();
+
+        assert_eq!(fs::read_to_string(folder.join("accounts.json")).unwrap(), accounts);
+        assert!(!fs::read_to_string(folder.join("launcher.json")).unwrap().contains("plaintext-key"));
+    }
+}
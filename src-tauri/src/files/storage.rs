@@ -1,6 +1,8 @@
 use std::fs;
 use std::sync::{Arc, RwLock};
 use home::home_dir;
+use crate::auth_route::skins::TextureCache;
+use crate::files::audit::AuditLog;
 use crate::version_manager::manager::AssetManager;
 use crate::files::errors::FileError;
 use crate::Settings;
@@ -12,6 +14,8 @@ pub struct Storage {
 pub struct InnerStorage {
     pub settings: Settings,
     pub assets: AssetManager,
+    pub textures: TextureCache,
+    pub audit: AuditLog,
 }
 
 impl Storage {
@@ -33,10 +37,20 @@ impl Storage {
         asset_manager.ensure_exists()
             .expect("Could not create asset manager");
 
+        let textures = TextureCache::new(&folder);
+        textures.ensure_exists()
+            .expect("Could not create texture cache");
+
+        let audit = AuditLog::new(&folder);
+        audit.ensure_exists()
+            .expect("Could not create audit log");
+
         Ok(Storage {
             inner: Arc::new(RwLock::new(InnerStorage {
                 settings,
-                assets: asset_manager
+                assets: asset_manager,
+                textures,
+                audit,
             })),
         })
     }
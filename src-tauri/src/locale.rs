@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+/// Languages the launcher is translated to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    #[serde(rename = "es")]
+    Spanish,
+    #[serde(rename = "en")]
+    English,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::Spanish
+    }
+}
+
+/// Texts shown by the backend, the frontend keeps its own translations.
+pub struct Catalog {
+    /// Value of the `lang` attribute of the pages.
+    pub code: &'static str,
+    pub login_window_title: &'static str,
+    pub login_success_title: &'static str,
+    pub login_success_message: &'static str,
+    pub login_failure_title: &'static str,
+    pub login_failure_message: &'static str,
+    pub login_closing_message: &'static str,
+}
+
+const SPANISH: Catalog = Catalog {
+    code: "es",
+    login_window_title: "Iniciar sesión",
+    login_success_title: "Sesión iniciada",
+    login_success_message: "Ya puedes volver al launcher.",
+    login_failure_title: "No se pudo iniciar sesión",
+    login_failure_message: "Vuelve al launcher para intentarlo de nuevo.",
+    login_closing_message: "Esta ventana se cerrará en unos segundos.",
+};
+
+const ENGLISH: Catalog = Catalog {
+    code: "en",
+    login_window_title: "Sign in",
+    login_success_title: "Signed in",
+    login_success_message: "You can return to the launcher.",
+    login_failure_title: "Could not sign in",
+    login_failure_message: "Return to the launcher to try again.",
+    login_closing_message: "This window will close in a few seconds.",
+};
+
+impl Language {
+    pub fn catalog(&self) -> &'static Catalog {
+        match self {
+            Language::Spanish => &SPANISH,
+            Language::English => &ENGLISH,
+        }
+    }
+}
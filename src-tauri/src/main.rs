@@ -5,15 +5,37 @@
 
 mod auth_route;
 mod files;
+mod locale;
 mod oauth_plugin;
 mod version_manager;
 
-use crate::auth_route::accounts::{Account, AccountStorage};
+#[cfg(test)]
+mod test_server;
+
+use crate::auth_route::accounts::{Account, AccountKind, AccountStorage};
+use crate::auth_route::errors::AuthError;
+use crate::files::instance::InstanceSettings;
+use crate::files::launcher::LauncherSettings;
 use crate::files::settings::Settings;
+use crate::locale::Language;
 use crate::files::storage::Storage;
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::curseforge::{self, CurseClient, CurseManifest, ManualDownload};
 use crate::version_manager::games::{Game, AVAILABLE_GAMES};
-use crate::version_manager::version::VersionId;
-use auth_route::auther;
+use crate::version_manager::archive::{self, ExportOptions};
+use crate::version_manager::instances::{Instance, InstanceStorage, Loader, PackSource};
+use crate::version_manager::launch::{LaunchOptions, QuickPlay};
+use crate::version_manager::mods::{self, ModReport};
+use crate::version_manager::mrpack::{self, PackIndex};
+use crate::version_manager::version::{VersionId, VersionSummary, VersionType};
+use crate::auth_route::skins::AccountTextures;
+use crate::auth_route::tokens::{
+    MinecraftProfile, NameAvailability, NameChangeEligibility, SkinVariant,
+};
+use crate::auth_route::yggdrasil::YggdrasilClient;
+use auth_route::{auther, offline, profile, refresher, signout, skins, yggdrasil};
+use std::fs;
+use std::path::PathBuf;
 use tauri::Manager;
 use tracing::{error, info};
 
@@ -25,27 +47,67 @@ async fn add_account(handle: tauri::AppHandle, window: tauri::Window) {
 }
 
 #[tauri::command]
-fn remove_account(handle: tauri::AppHandle, account: u32) {
+fn add_offline_account(handle: tauri::AppHandle, username: String) -> Result<(), String> {
     let storage = handle.state::<Storage>().inner().extract();
     let mut storage = storage.write().unwrap();
 
+    if !storage.settings.launcher.contents.allow_offline_accounts {
+        return Err(AuthError::OfflineAccountsDisabled.to_string());
+    }
+
+    let account = offline::create_account(&username).map_err(|err| err.to_string())?;
+
     let store = &mut storage.settings.accounts.contents;
+    store.elected_account = Some(account.profile.id.clone());
+    store.upsert(account);
 
-    let remove_elected = if let Some(account) = store.accounts.get(account as usize) {
-        if let Some(elected) = &store.elected_account {
-            elected == &account.profile.id
-        } else {
-            false
-        }
-    } else {
-        false
-    };
+    storage
+        .settings
+        .accounts
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn add_yggdrasil_account(
+    handle: tauri::AppHandle,
+    server: String,
+    username: String,
+    password: String,
+) -> Result<(), String> {
+    let account = yggdrasil::create_account(&server, &username, &password)
+        .await
+        .map_err(|err| err.to_string())?;
 
-    store.accounts.remove(account as usize);
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
 
-    if remove_elected {
-        store.elected_account = None;
-    }
+    let store = &mut storage.settings.accounts.contents;
+    store.elected_account = Some(account.profile.id.clone());
+    store.upsert(account);
+
+    storage
+        .settings
+        .accounts
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn yggdrasil_signout(server: String, username: String, password: String) -> Result<(), String> {
+    YggdrasilClient::resolve(&server)
+        .await
+        .map_err(|err| err.to_string())?
+        .signout(&username, &password)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn remove_account(handle: tauri::AppHandle, account: String) -> Result<(), String> {
+    signout::sign_out(&handle, &account)
+        .await
+        .map_err(|err| err.to_string())
 }
 
 #[tauri::command]
@@ -57,16 +119,161 @@ fn get_accounts(handle: tauri::AppHandle) -> AccountStorage {
 }
 
 #[tauri::command]
-async fn elect_account(handle: tauri::AppHandle, account: u32) {
+fn elect_account(handle: tauri::AppHandle, account: String) -> Result<(), String> {
     let storage = handle.state::<Storage>().inner().extract();
     let mut storage = storage.write().unwrap();
-    let accounts = &mut storage.settings.accounts.contents;
-    let account = accounts.accounts.get(account as usize);
 
-    if let Some(account) = account {
-        accounts.elected_account = Some(account.profile.id.clone());
-        storage.settings.accounts.save();
-    }
+    storage
+        .settings
+        .accounts
+        .contents
+        .elect(&account)
+        .map_err(|err| err.to_string())?;
+
+    storage
+        .settings
+        .accounts
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn list_textures(
+    handle: tauri::AppHandle,
+    account: String,
+) -> Result<MinecraftProfile, String> {
+    skins::list_textures(&handle, &account)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn upload_skin(
+    handle: tauri::AppHandle,
+    account: String,
+    path: PathBuf,
+    variant: SkinVariant,
+) -> Result<MinecraftProfile, String> {
+    skins::upload_skin(&handle, &account, path, variant)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn set_skin_url(
+    handle: tauri::AppHandle,
+    account: String,
+    url: String,
+    variant: SkinVariant,
+) -> Result<MinecraftProfile, String> {
+    skins::set_skin_url(&handle, &account, &url, variant)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn reset_skin(handle: tauri::AppHandle, account: String) -> Result<MinecraftProfile, String> {
+    skins::reset_skin(&handle, &account)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn show_cape(
+    handle: tauri::AppHandle,
+    account: String,
+    cape: String,
+) -> Result<MinecraftProfile, String> {
+    skins::show_cape(&handle, &account, &cape)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn hide_cape(handle: tauri::AppHandle, account: String) -> Result<MinecraftProfile, String> {
+    skins::hide_cape(&handle, &account)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn get_account_textures(
+    handle: tauri::AppHandle,
+    account: String,
+) -> Result<AccountTextures, String> {
+    skins::cached_textures(&handle, &account)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn get_name_change_eligibility(
+    handle: tauri::AppHandle,
+    account: String,
+) -> Result<NameChangeEligibility, String> {
+    profile::name_change_eligibility(&handle, &account)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn check_name_availability(
+    handle: tauri::AppHandle,
+    account: String,
+    name: String,
+) -> Result<NameAvailability, String> {
+    profile::name_availability(&handle, &account, &name)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn change_name(
+    handle: tauri::AppHandle,
+    account: String,
+    name: String,
+) -> Result<MinecraftProfile, String> {
+    profile::change_name(&handle, &account, &name)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn get_launcher_settings(handle: tauri::AppHandle) -> LauncherSettings {
+    let storage = handle.state::<Storage>().inner().extract();
+    let storage = storage.read().unwrap();
+
+    storage.settings.launcher.contents.clone()
+}
+
+#[tauri::command]
+fn set_language(handle: tauri::AppHandle, language: Language) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    storage.settings.launcher.contents.language = language;
+    storage
+        .settings
+        .launcher
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn list_versions(
+    handle: tauri::AppHandle,
+    types: Option<Vec<VersionType>>,
+) -> Result<Vec<VersionSummary>, String> {
+    let assets = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let storage = storage.read().unwrap();
+        storage.assets.clone()
+    };
+
+    assets
+        .list_versions(&types.unwrap_or_default())
+        .await
+        .map_err(|err| err.to_string())
 }
 
 #[tauri::command]
@@ -90,14 +297,556 @@ fn pick_game(handle: tauri::AppHandle, id: String) {
     if let Some(game) = game {
         storage.settings.games.contents.elected_game = Some(game.0.to_string());
         storage.settings.games.save();
+
+        if storage.settings.instances.contents.elect(game.0).is_ok() {
+            if let Err(err) = storage.settings.instances.save() {
+                error!("Could not save instances: {}", err);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+fn get_instances(handle: tauri::AppHandle) -> InstanceStorage {
+    let storage = handle.state::<Storage>().inner().extract();
+    let storage = storage.read().unwrap();
+
+    storage.settings.instances.contents.clone()
+}
+
+#[tauri::command]
+fn pick_instance(handle: tauri::AppHandle, id: String) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    storage
+        .settings
+        .instances
+        .contents
+        .elect(&id)
+        .map_err(|err| err.to_string())?;
+    storage
+        .settings
+        .instances
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn create_instance(
+    handle: tauri::AppHandle,
+    name: String,
+    version: VersionId,
+    loader: Option<Loader>,
+    icon: Option<String>,
+) -> Result<Instance, String> {
+    let loader = loader.unwrap_or_default();
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    let id = storage
+        .settings
+        .instances
+        .contents
+        .unique_id(&name, |id| storage.assets.instance_path(id).exists());
+    let mut instance = Instance::new(
+        id,
+        name,
+        version,
+        loader,
+        PackSource::Custom,
+    );
+    instance.icon = icon;
+
+    fs::create_dir_all(storage.assets.instance_path(&instance.id))
+        .map_err(|err| err.to_string())?;
+
+    storage.settings.instances.contents.insert(instance.clone());
+    storage
+        .settings
+        .instances
+        .save()
+        .map_err(|err| err.to_string())?;
+
+    info!("Created instance {}", instance.id);
+    Ok(instance)
+}
+
+#[tauri::command]
+fn duplicate_instance(handle: tauri::AppHandle, id: String, name: String) -> Result<Instance, String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    let source = storage
+        .settings
+        .instances
+        .contents
+        .find(&id)
+        .map_err(|err| err.to_string())?
+        .clone();
+
+    let mut instance = Instance::new(
+        storage
+            .settings
+            .instances
+            .contents
+            .unique_id(&name, |id| storage.assets.instance_path(id).exists()),
+        name,
+        source.version,
+        source.loader,
+        source.source,
+    );
+    instance.icon = source.icon;
+
+    storage
+        .assets
+        .copy_instance(&id, &instance.id)
+        .map_err(|err| err.to_string())?;
+
+    storage.settings.instances.contents.insert(instance.clone());
+    storage
+        .settings
+        .instances
+        .save()
+        .map_err(|err| err.to_string())?;
+
+    info!("Duplicated instance {} as {}", id, instance.id);
+    Ok(instance)
+}
+
+#[tauri::command]
+fn update_instance(
+    handle: tauri::AppHandle,
+    id: String,
+    name: String,
+    icon: Option<String>,
+) -> Result<Instance, String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    // Renaming keeps the id, so the directory stays where it is
+    let instance = storage
+        .settings
+        .instances
+        .contents
+        .find_mut(&id)
+        .map_err(|err| err.to_string())?;
+    instance.name = name;
+    instance.icon = icon;
+    let instance = instance.clone();
+
+    storage
+        .settings
+        .instances
+        .save()
+        .map_err(|err| err.to_string())?;
+
+    Ok(instance)
+}
+
+#[tauri::command]
+fn delete_instance(handle: tauri::AppHandle, id: String) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    storage
+        .settings
+        .instances
+        .contents
+        .find(&id)
+        .map_err(|err| err.to_string())?;
+
+    if let Some(trashed) = storage
+        .assets
+        .trash_instance(&id)
+        .map_err(|err| err.to_string())?
+    {
+        info!("Moved instance {} to {:?}", id, trashed);
+    }
+
+    storage
+        .settings
+        .instances
+        .contents
+        .remove(&id)
+        .map_err(|err| err.to_string())?;
+    storage
+        .settings
+        .instances
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn export_instance(
+    handle: tauri::AppHandle,
+    id: String,
+    path: PathBuf,
+    options: Option<ExportOptions>,
+) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let storage = storage.read().unwrap();
+
+    let instance = storage
+        .settings
+        .instances
+        .contents
+        .find(&id)
+        .map_err(|err| err.to_string())?;
+    let instance_path = storage.assets.instance_path(&id);
+    let settings = InstanceSettings::open(&instance_path).map_err(|err| err.to_string())?;
+
+    archive::export(
+        instance,
+        &instance_path,
+        &settings.contents,
+        &options.unwrap_or_default(),
+        &path,
+    )
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn import_instance(handle: tauri::AppHandle, path: PathBuf) -> Result<Instance, String> {
+    let descriptor = archive::read_descriptor(&path).map_err(|err| err.to_string())?;
+
+    let (instance, assets) = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let mut storage = storage.write().unwrap();
+
+        let id = storage
+            .settings
+            .instances
+            .contents
+            .unique_id(&descriptor.name, |id| storage.assets.instance_path(id).exists());
+        let instance_path = storage.assets.instance_path(&id);
+
+        archive::extract(&path, &instance_path).map_err(|err| err.to_string())?;
+
+        let (instance, settings) = descriptor.into_instance(id);
+
+        let saved = InstanceSettings::open(&instance_path).and_then(|mut file| {
+            file.contents = settings;
+            file.save()
+        });
+        if let Err(err) = saved {
+            if let Err(err) = fs::remove_dir_all(&instance_path) {
+                error!("Could not remove {:?}: {}", instance_path, err);
+            }
+            return Err(err.to_string());
+        }
+
+        storage.settings.instances.contents.insert(instance.clone());
+        storage
+            .settings
+            .instances
+            .save()
+            .map_err(|err| err.to_string())?;
+
+        (instance, storage.assets.clone())
+    };
+
+    info!("Imported instance {} from {:?}", instance.id, path);
+
+    // Launching downloads whatever is still missing, so the instance is kept anyway
+    if let Err(err) = assets.install_version(&instance.version, &instance.loader).await {
+        error!("Could not download version {}: {}", instance.version, err);
+    }
+
+    Ok(instance)
+}
+
+/// Drops an instance whose import failed halfway.
+fn discard_instance(handle: &tauri::AppHandle, id: &str) {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    if let Err(err) = storage.assets.trash_instance(id) {
+        error!("Could not remove {}: {}", id, err);
+    }
+    if storage.settings.instances.contents.remove(id).is_ok() {
+        if let Err(err) = storage.settings.instances.save() {
+            error!("Could not save instances: {}", err);
+        }
+    }
+}
+
+#[tauri::command]
+async fn import_mrpack(handle: tauri::AppHandle, path: PathBuf) -> Result<Instance, String> {
+    let index = PackIndex::read(&path).map_err(|err| err.to_string())?;
+    let version = index.minecraft_version().map_err(|err| err.to_string())?;
+    let loader = index.loader();
+
+    // The pack is only worth downloading once its loader is installed
+    let assets = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let storage = storage.read().unwrap();
+        storage.assets.clone()
+    };
+    assets
+        .install_version(&version, &loader)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let (instance, instance_path) = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let mut storage = storage.write().unwrap();
+
+        let id = storage
+            .settings
+            .instances
+            .contents
+            .unique_id(&index.name, |id| storage.assets.instance_path(id).exists());
+        let instance_path = storage.assets.instance_path(&id);
+
+        fs::create_dir(&instance_path).map_err(|err| err.to_string())?;
+
+        let source = PackSource::Modrinth {
+            name: index.name.clone(),
+            version: index.version_id.clone(),
+        };
+        let instance = Instance::new(id, index.name.clone(), version, loader, source);
+
+        storage.settings.instances.contents.insert(instance.clone());
+        storage
+            .settings
+            .instances
+            .save()
+            .map_err(|err| err.to_string())?;
+
+        (instance, instance_path)
+    };
+
+    let installed = match mrpack::apply_overrides(&path, &instance_path) {
+        Ok(_) => mrpack::download_files(&reqwest::Client::new(), &index, &instance_path).await,
+        Err(err) => Err(err),
+    };
+
+    // A pack missing some of its files is of no use, so nothing is left behind
+    if let Err(err) = installed {
+        discard_instance(&handle, &instance.id);
+        return Err(err.to_string());
     }
+
+    info!("Imported modpack {} {} as {}", index.name, index.version_id, instance.id);
+
+    Ok(instance)
+}
+
+#[derive(serde::Serialize)]
+struct CurseImport {
+    instance: Instance,
+    /// Files to download by hand, their authors don't allow launchers to.
+    manual_downloads: Vec<ManualDownload>,
 }
 
 #[tauri::command]
-async fn start_game(handle: tauri::AppHandle) {
+async fn import_curseforge(handle: tauri::AppHandle, path: PathBuf) -> Result<CurseImport, String> {
+    let manifest = CurseManifest::read(&path).map_err(|err| err.to_string())?;
+    let loader = manifest.loader().map_err(|err| err.to_string())?;
+
+    // The pack is only worth downloading once its loader is installed
+    let assets = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let storage = storage.read().unwrap();
+        storage.assets.clone()
+    };
+    assets
+        .install_version(&manifest.minecraft_version(), &loader)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let (instance, instance_path, client) = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let mut storage = storage.write().unwrap();
+
+        let launcher = &storage.settings.launcher.contents;
+        let client = CurseClient::new(
+            &launcher.curseforge_api_url,
+            launcher.curseforge_api_key.as_deref(),
+        )
+        .map_err(|err| err.to_string())?;
+
+        let id = storage
+            .settings
+            .instances
+            .contents
+            .unique_id(&manifest.name, |id| storage.assets.instance_path(id).exists());
+        let instance_path = storage.assets.instance_path(&id);
+
+        fs::create_dir(&instance_path).map_err(|err| err.to_string())?;
+
+        let source = PackSource::CurseForge {
+            name: manifest.name.clone(),
+            version: manifest.version.clone(),
+        };
+        let instance = Instance::new(
+            id,
+            manifest.name.clone(),
+            manifest.minecraft_version(),
+            loader,
+            source,
+        );
+
+        storage.settings.instances.contents.insert(instance.clone());
+        storage
+            .settings
+            .instances
+            .save()
+            .map_err(|err| err.to_string())?;
+
+        (instance, instance_path, client)
+    };
+
+    let installed = match curseforge::apply_overrides(&path, &manifest, &instance_path) {
+        Ok(_) => client.download_files(&manifest, &instance_path).await,
+        Err(err) => Err(err),
+    };
+
+    let manual_downloads = match installed {
+        Ok(manual_downloads) => manual_downloads,
+        Err(err) => {
+            discard_instance(&handle, &instance.id);
+            return Err(err.to_string());
+        }
+    };
+
+    info!("Imported modpack {} as {}", manifest.name, instance.id);
+
+    Ok(CurseImport {
+        instance,
+        manual_downloads,
+    })
+}
+
+#[tauri::command]
+fn set_curseforge_api(
+    handle: tauri::AppHandle,
+    url: Option<String>,
+    key: Option<String>,
+) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    let launcher = &mut storage.settings.launcher.contents;
+    if let Some(url) = url {
+        launcher.curseforge_api_url = url;
+    }
+    launcher.curseforge_api_key = key;
+
+    storage
+        .settings
+        .launcher
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn list_mods(handle: tauri::AppHandle, id: String) -> Result<ModReport, String> {
+    let instance_path = {
+        let storage = handle.state::<Storage>().inner().extract();
+        let storage = storage.read().unwrap();
+
+        storage
+            .settings
+            .instances
+            .contents
+            .find(&id)
+            .map_err(|err| err.to_string())?;
+        storage.assets.instance_path(&id)
+    };
+
+    mods::list(&instance_path)
+        .map(ModReport::new)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn set_mod_enabled(
+    handle: tauri::AppHandle,
+    id: String,
+    file_name: String,
+    enabled: bool,
+) -> Result<String, String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let storage = storage.read().unwrap();
+
+    storage
+        .settings
+        .instances
+        .contents
+        .find(&id)
+        .map_err(|err| err.to_string())?;
+
+    mods::set_enabled(&storage.assets.instance_path(&id), &file_name, enabled)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn get_instance_settings(handle: tauri::AppHandle, id: String) -> Result<InstanceSettings, String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let storage = storage.read().unwrap();
+
+    storage
+        .settings
+        .instances
+        .contents
+        .find(&id)
+        .map_err(|err| err.to_string())?;
+
+    InstanceSettings::open(&storage.assets.instance_path(&id))
+        .map(|settings| settings.contents)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn set_instance_settings(
+    handle: tauri::AppHandle,
+    id: String,
+    settings: InstanceSettings,
+) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let storage = storage.read().unwrap();
+
+    storage
+        .settings
+        .instances
+        .contents
+        .find(&id)
+        .map_err(|err| err.to_string())?;
+
+    settings.validate().map_err(|err| err.to_string())?;
+
+    let mut file = InstanceSettings::open(&storage.assets.instance_path(&id))
+        .map_err(|err| err.to_string())?;
+    file.contents = settings;
+    file.save().map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+fn set_instance_defaults(handle: tauri::AppHandle, settings: InstanceSettings) -> Result<(), String> {
+    let storage = handle.state::<Storage>().inner().extract();
+    let mut storage = storage.write().unwrap();
+
+    settings.validate().map_err(|err| err.to_string())?;
+
+    storage.settings.launcher.contents.instance_defaults = settings;
+    storage
+        .settings
+        .launcher
+        .save()
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn start_game(
+    handle: tauri::AppHandle,
+    instance: Option<String>,
+    quick_play: Option<QuickPlay>,
+) -> Result<(), String> {
     let source = handle.state::<Storage>().inner();
 
-    let data = {
+    let (mut account, mut identity, assets, instance, options) = {
         let storage = source.extract();
         let storage = storage.read().unwrap();
 
@@ -105,60 +854,86 @@ async fn start_game(handle: tauri::AppHandle) {
 
         let accounts = &storage.settings.accounts.contents;
         let games = &storage.settings.games.contents;
+        let instances = &storage.settings.instances.contents;
+        let launcher = &storage.settings.launcher.contents;
 
-        // Awful "hack" to get the elected account
-        if let Some(account) = &accounts.elected_account {
-            if let Some(account) = accounts.accounts.iter().find(|x| &x.profile.id == account) {
-                if let Some(game) = &games.elected_game {
-                    if let Some(game) = AVAILABLE_GAMES.iter().find(|it| it.0 == game.as_str()) {
-                        let game = Game::from_static(game);
-                        Some((account.clone(), assets, game))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
+        let account = accounts
+            .elected_account
+            .as_ref()
+            .ok_or_else(|| AuthError::NoElectedAccount.to_string())
+            .and_then(|id| accounts.find(id).map_err(|err| err.to_string()))?;
+
+        if account.kind == AccountKind::Offline && !launcher.allow_offline_accounts {
+            return Err(AuthError::OfflineAccountsDisabled.to_string());
         }
+
+        // Games map to the instance with their id
+        let instance = instance
+            .or_else(|| instances.elected_instance.clone())
+            .or_else(|| games.elected_game.clone())
+            .ok_or_else(|| ManagerError::NoElectedInstance.to_string())?;
+        let instance = instances
+            .find(&instance)
+            .map_err(|err| err.to_string())?
+            .clone();
+
+        let settings = match InstanceSettings::open(&assets.instance_path(&instance.id)) {
+            Ok(settings) => settings.contents,
+            Err(err) => {
+                error!("Could not load instance settings: {}", err);
+                InstanceSettings::default()
+            }
+        };
+        let quick_play = quick_play.or_else(|| {
+            instance
+                .game()
+                .and_then(|game| game.server)
+                .map(|address| QuickPlay::Multiplayer { address })
+        });
+        let options = LaunchOptions::new(
+            settings.merged(&launcher.instance_defaults),
+            quick_play,
+            launcher.strict_arguments,
+        );
+        let identity = account
+            .identity
+            .as_ref()
+            .and_then(|identity| accounts.identity(identity))
+            .cloned();
+
+        (account.clone(), identity, assets, instance, options)
     };
 
-    if let Some((mut account, assets, game)) = data {
-        let should_save = auther::validate_token(&mut account).await;
-
-        {
-            match should_save {
-                Err(err) => error!("Error while validating token: {}", err),
-                Ok(should_save) => {
-                    if should_save {
-                        info!("Token has been updated");
-                        let storage = source.extract();
-                        let mut storage = storage.write().unwrap();
-                        let pos = storage
-                            .settings
-                            .accounts
-                            .contents
-                            .accounts
-                            .iter_mut()
-                            .position(|x| x.profile.id == account.profile.id)
-                            .unwrap();
-                        storage.settings.accounts.contents.accounts[pos] = account.clone();
-                        storage.settings.accounts.save();
+    let should_save = auther::validate_token(&mut account, identity.as_mut()).await;
+
+    {
+        match should_save {
+            Err(err) => error!("Error while validating token: {}", err),
+            Ok(should_save) => {
+                if should_save {
+                    info!("Token has been updated");
+                    let storage = source.extract();
+                    let mut storage = storage.write().unwrap();
+                    storage
+                        .settings
+                        .accounts
+                        .contents
+                        .store_session(account.clone(), identity.clone());
+                    if let Err(err) = storage.settings.accounts.save() {
+                        error!("Could not save accounts: {}", err);
                     }
                 }
             }
         }
+    }
 
-        let res = assets.load_version(game, account).await;
-
-        if let Err(err) = res {
+    assets
+        .load_version(instance, account, identity, options)
+        .await
+        .map_err(|err| {
             error!("{:?}", err);
-        }
-    }
+            err.to_string()
+        })
 }
 
 fn main() {
@@ -168,12 +943,44 @@ fn main() {
         .plugin(oauth_plugin::init())
         .invoke_handler(tauri::generate_handler![
             add_account,
+            add_offline_account,
+            add_yggdrasil_account,
+            yggdrasil_signout,
             get_accounts,
             remove_account,
             elect_account,
+            list_textures,
+            upload_skin,
+            set_skin_url,
+            reset_skin,
+            show_cape,
+            hide_cape,
+            get_account_textures,
+            get_name_change_eligibility,
+            check_name_availability,
+            change_name,
             start_game,
+            get_launcher_settings,
+            set_language,
+            list_versions,
             get_games,
-            pick_game
+            pick_game,
+            get_instances,
+            pick_instance,
+            create_instance,
+            duplicate_instance,
+            update_instance,
+            delete_instance,
+            export_instance,
+            import_instance,
+            import_mrpack,
+            import_curseforge,
+            set_curseforge_api,
+            list_mods,
+            set_mod_enabled,
+            get_instance_settings,
+            set_instance_settings,
+            set_instance_defaults
         ])
         .setup(|app| {
             info!("Initializing Launcher");
@@ -182,6 +989,8 @@ fn main() {
             info!("Storage initialized");
             app.manage(storage);
 
+            refresher::start(app.handle());
+
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -1,20 +1,53 @@
 use std::{
     borrow::Cow,
-    io::{Read, Write},
-    net::{SocketAddr, TcpListener, TcpStream},
-    thread,
+    collections::HashMap,
+    io,
+    net::{SocketAddr, TcpListener as StdTcpListener},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use tauri::{
     plugin::{Builder, TauriPlugin},
     Runtime,
 };
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Requests larger than this are rejected, a redirect only carries a few parameters.
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+const MAX_HEADERS: usize = 32;
+/// Time a client has to send its whole request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Providers match the registered redirect uri including its path, so it can't vary per session.
+const DEFAULT_PATH: &str = "/";
+const CALLBACK_PATH: &str = "cb";
+const FULL_URL_HEADER: &str = "Full-Url";
+
+/// Token of every running server, so they can be cancelled by port.
+static SERVERS: Lazy<Mutex<HashMap<u16, CancellationToken>>> = Lazy::new(Default::default);
 
-const EXIT: [u8; 4] = [1, 3, 3, 7];
+/// A running server, only requests to its path are handled.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OauthSession {
+    pub port: u16,
+    /// Path the redirect must point to, `/` unless configured.
+    pub path: String,
+    pub redirect_uri: String,
+}
 
-/// Starts the localhost (using 127.0.0.1) server. Returns the port its listening on.
+/// Starts the localhost (using 127.0.0.1) server. Returns the session describing where it listens.
 ///
-/// Because of the unprotected localhost port, you _must_ verify the URL in the handler function.
+/// The redirect uri of the OAuth provider must be `session.redirect_uri`, requests to any
+/// other path are ignored. When `OauthConfig::state` is set, redirects without that state
+/// are ignored as well, so other local processes can't inject URLs or end the session.
 ///
 /// # Arguments
 ///
@@ -23,11 +56,7 @@ const EXIT: [u8; 4] = [1, 3, 3, 7];
 /// # Errors
 ///
 /// - Returns `std::io::Error` if the server creation fails.
-///
-/// # Panics
-///
-/// The seperate server thread can panic if its unable to send the html response to the client. This may change after more real world testing.
-pub fn start<F: FnMut(String) + Send + 'static>(handler: F) -> Result<u16, std::io::Error> {
+pub fn start<F: FnMut(String) + Send + 'static>(handler: F) -> Result<OauthSession, io::Error> {
     start_with_config(OauthConfig::default(), handler)
 }
 
@@ -44,108 +73,384 @@ pub struct OauthConfig {
     ///
     /// Default: `"<html><body>Please return to the app.</body></html>"`.
     pub response: Option<Cow<'static, str>>,
+    /// Optional static html string send instead of `response` when the provider
+    /// redirects with an `error` parameter.
+    ///
+    /// Default: `response`.
+    pub failure_response: Option<Cow<'static, str>>,
+    /// Path of the redirect uri registered with the provider.
+    ///
+    /// Default: `"/"`.
+    pub path: Option<Cow<'static, str>>,
+    /// The `state` sent to the provider, which must come back with the redirect.
+    ///
+    /// Default: Any redirect is handled, the handler has to verify the state.
+    pub state: Option<String>,
 }
 
-/// Starts the localhost (using 127.0.0.1) server. Returns the port its listening on.
+/// Starts the localhost (using 127.0.0.1) server. Returns the session describing where it listens.
 ///
-/// Because of the unprotected localhost port, you _must_ verify the URL in the handler function.
+/// See [`start`] for the requests the server accepts.
 ///
 /// # Arguments
 ///
-/// * `config` - Configuration the server should use, see [`OauthConfig.]
+/// * `config` - Configuration the server should use, see [`OauthConfig`].
 /// * `handler` - Closure which will be executed on a successful connection. It receives the full URL as a String.
 ///
 /// # Errors
 ///
 /// - Returns `std::io::Error` if the server creation fails.
-///
-/// # Panics
-///
-/// The seperate server thread can panic if its unable to send the html response to the client. This may change after more real world testing.
 pub fn start_with_config<F: FnMut(String) + Send + 'static>(
     config: OauthConfig,
     mut handler: F,
-) -> Result<u16, std::io::Error> {
+) -> Result<OauthSession, io::Error> {
+    start_deferred(config, move |url, completion| {
+        let failed = is_failure(&url);
+        handler(url);
+        completion.complete(!failed);
+    })
+}
+
+/// Like [`start_with_config`], but the page is only sent once the handler calls
+/// [`Completion::complete`], so it can reflect whether the login went through.
+///
+/// # Errors
+///
+/// - Returns `std::io::Error` if the server creation fails.
+pub fn start_deferred<F: FnMut(String, Completion) + Send + 'static>(
+    config: OauthConfig,
+    handler: F,
+) -> Result<OauthSession, io::Error> {
     let listener = match config.ports {
-        Some(ports) => TcpListener::bind(
+        Some(ports) => StdTcpListener::bind(
             ports
                 .iter()
                 .map(|p| SocketAddr::from(([127, 0, 0, 1], *p)))
                 .collect::<Vec<SocketAddr>>()
                 .as_slice(),
         ),
-        None => TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))),
+        None => StdTcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))),
     }?;
+    listener.set_nonblocking(true)?;
 
     let port = listener.local_addr()?.port();
 
-    thread::spawn(move || {
-        for conn in listener.incoming() {
-            match conn {
-                Ok(conn) => {
-                    if let Some(url) = handle_connection(conn, config.response.as_deref(), port) {
-                        // Using an empty string to communicate that a shutdown was requested.
-                        if !url.is_empty() {
-                            handler(url);
-                        }
-                        // TODO: Check if exiting here is always okay.
-                        break;
-                    }
+    let path = config.path.as_deref().unwrap_or(DEFAULT_PATH);
+    if !path.starts_with('/') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The redirect path {} must start with /", path),
+        ));
+    }
+
+    let server = Arc::new(Server {
+        port,
+        path: path.to_string(),
+        state: config.state,
+        response: config.response,
+        failure_response: config.failure_response,
+        read_timeout: READ_TIMEOUT,
+    });
+
+    let token = CancellationToken::new();
+    SERVERS.lock().unwrap().insert(port, token.clone());
+
+    let session = OauthSession {
+        port,
+        path: server.path.clone(),
+        redirect_uri: format!("http://localhost:{}{}", port, server.path),
+    };
+
+    tauri::async_runtime::spawn(async move {
+        match TcpListener::from_std(listener) {
+            Ok(listener) => serve(listener, server, token, handler).await,
+            Err(err) => log::error!("Error starting the oauth server: {}", err),
+        }
+
+        SERVERS.lock().unwrap().remove(&port);
+    });
+
+    Ok(session)
+}
+
+/// Pending answer to the redirect, the browser keeps waiting until it is completed.
+/// Dropping it sends the failure page.
+#[derive(Debug)]
+pub struct Completion(oneshot::Sender<bool>);
+
+impl Completion {
+    /// Sends `response`, or `failure_response` when the login did not succeed.
+    pub fn complete(self, succeeded: bool) {
+        self.0.send(succeeded).ok();
+    }
+}
+
+struct Server {
+    port: u16,
+    path: String,
+    state: Option<String>,
+    response: Option<Cow<'static, str>>,
+    failure_response: Option<Cow<'static, str>>,
+    read_timeout: Duration,
+}
+
+impl Server {
+    fn callback_path(&self) -> String {
+        format!("{}/{}", self.path.trim_end_matches('/'), CALLBACK_PATH)
+    }
+
+    /// Whether the redirect carries the state of the session, in its query or its fragment.
+    fn has_state(&self, url: &url::Url) -> bool {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return true,
+        };
+
+        let fragment = url
+            .fragment()
+            .map(|fragment| url::form_urlencoded::parse(fragment.as_bytes()))
+            .into_iter()
+            .flatten();
+
+        url.query_pairs()
+            .chain(fragment)
+            .any(|(key, value)| key == "state" && value == state.as_str())
+    }
+}
+
+struct Request {
+    method: String,
+    target: String,
+    host: Option<String>,
+    full_url: Option<String>,
+}
+
+#[derive(Debug)]
+enum RequestError {
+    Io(io::Error),
+    Malformed,
+    TooLarge,
+    TimedOut,
+}
+
+enum Route {
+    /// The redirect itself, carrying the parameters in its query.
+    Redirect(String),
+    /// The redirect without query, the parameters could be in the fragment.
+    Page,
+    Ignored(&'static str),
+}
+
+async fn serve<F: FnMut(String, Completion) + Send + 'static>(
+    listener: TcpListener,
+    server: Arc<Server>,
+    token: CancellationToken,
+    mut handler: F,
+) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => break,
+            Some((url, completion)) = receiver.recv() => {
+                handler(url, completion);
+                token.cancel();
+                break;
+            }
+            conn = listener.accept() => match conn {
+                Ok((stream, _)) => {
+                    let server = server.clone();
+                    let sender = sender.clone();
+                    let token = token.child_token();
+
+                    // Each connection is handled on its own, so a client that
+                    // never finishes its request doesn't block the redirect.
+                    tauri::async_runtime::spawn(async move {
+                        handle_connection(stream, &server, token, sender).await;
+                    });
                 }
                 Err(err) => {
-                    log::error!("Error reading incoming connection: {}", err.to_string());
+                    log::error!("Error reading incoming connection: {}", err);
                 }
             }
         }
-    });
-
-    Ok(port)
+    }
 }
 
-fn handle_connection(mut conn: TcpStream, response: Option<&str>, port: u16) -> Option<String> {
-    let mut buffer = [0; 4048];
-    if let Err(io_err) = conn.read(&mut buffer) {
-        log::error!("Error reading incoming connection: {}", io_err.to_string());
+async fn handle_connection(
+    mut conn: TcpStream,
+    server: &Server,
+    token: CancellationToken,
+    sender: mpsc::UnboundedSender<(String, Completion)>,
+) {
+    // Requests still being read when the server stops are dropped,
+    //   the redirect keeps its connection until it is completed.
+    let request = tokio::select! {
+        _ = token.cancelled() => return,
+        request = tokio::time::timeout(server.read_timeout, read_request(&mut conn)) => match request {
+            Ok(request) => request,
+            Err(_) => Err(RequestError::TimedOut),
+        },
     };
-    if buffer[..4] == EXIT {
-        return Some(String::new());
+
+    let request = match request {
+        Ok(request) => request,
+        Err(RequestError::Io(err)) => {
+            log::error!("Error reading incoming connection: {}", err);
+            return;
+        }
+        Err(RequestError::TimedOut) => return,
+        Err(RequestError::TooLarge) => {
+            write_response(&mut conn, "431 Request Header Fields Too Large", "").await;
+            return;
+        }
+        Err(RequestError::Malformed) => {
+            write_response(&mut conn, "400 Bad Request", "").await;
+            return;
+        }
+    };
+
+    match route(&request, server) {
+        Route::Redirect(url) => {
+            let (completion, answer) = oneshot::channel();
+            if sender.send((url, Completion(completion))).is_err() {
+                return;
+            }
+
+            // A dropped completion, e.g. when another redirect won, counts as a failure.
+            let succeeded = answer.await.unwrap_or(false);
+            let page = completion_page(server, None, !succeeded);
+            write_response(&mut conn, "200 OK", &page).await;
+        }
+        Route::Page => {
+            let script = callback_script(server, &request);
+            let page = completion_page(server, Some(&script), false);
+            write_response(&mut conn, "200 OK", &page).await;
+        }
+        Route::Ignored(status) => {
+            write_response(&mut conn, status, "").await;
+        }
     }
+}
 
-    let mut headers = [httparse::EMPTY_HEADER; 16];
-    let mut request = httparse::Request::new(&mut headers);
-    request.parse(&buffer).ok()?;
+/// Reads until the request head is complete, the body is never needed.
+async fn read_request(conn: &mut TcpStream) -> Result<Request, RequestError> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0; 1024];
 
-    let path = request.path.unwrap_or_default();
+    loop {
+        let read = conn.read(&mut chunk).await.map_err(RequestError::Io)?;
 
-    if path == "/exit" {
-        return Some(String::new());
-    };
+        if read == 0 {
+            return Err(RequestError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
 
-    let mut is_localhost = false;
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
 
-    for header in &headers {
-        if header.name == "Full-Url" {
-            return Some(String::from_utf8_lossy(header.value).to_string());
-        } else if header.name == "Host" {
-            is_localhost = String::from_utf8_lossy(header.value).starts_with("localhost");
+        match request.parse(&buffer) {
+            Ok(httparse::Status::Complete(_)) => {
+                let header = |name: &str| {
+                    request
+                        .headers
+                        .iter()
+                        .find(|header| header.name.eq_ignore_ascii_case(name))
+                        .map(|header| String::from_utf8_lossy(header.value).to_string())
+                };
+
+                return Ok(Request {
+                    method: request.method.unwrap_or_default().to_string(),
+                    target: request.path.unwrap_or_default().to_string(),
+                    host: header("Host"),
+                    full_url: header(FULL_URL_HEADER),
+                });
+            }
+            Ok(httparse::Status::Partial) if buffer.len() < MAX_REQUEST_SIZE => continue,
+            Ok(httparse::Status::Partial) | Err(httparse::Error::TooManyHeaders) => {
+                return Err(RequestError::TooLarge)
+            }
+            Err(_) => return Err(RequestError::Malformed),
         }
     }
-    if path == "/cb" {
-        log::error!(
-            "Client fetched callback path but the request didn't contain the expected header."
-        );
+}
+
+fn route(request: &Request, server: &Server) -> Route {
+    let (path, query) = match request.target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.target.as_str(), None),
+    };
+
+    if path == server.path {
+        return match (request.method.as_str(), query) {
+            ("GET", Some(query)) if !query.is_empty() => {
+                let url = format!("http://localhost:{}{}", server.port, request.target);
+
+                match url::Url::parse(&url) {
+                    Ok(parsed) if server.has_state(&parsed) => Route::Redirect(url),
+                    _ => Route::Ignored("400 Bad Request"),
+                }
+            }
+            ("GET", _) => Route::Page,
+            _ => Route::Ignored("405 Method Not Allowed"),
+        };
+    }
+
+    if path == server.callback_path() {
+        // Only our own page calls back, and the url it reports must be ours as well.
+        let url = request
+            .full_url
+            .as_deref()
+            .and_then(|url| url::Url::parse(url).ok())
+            .filter(|url| url.path() == server.path && url.port() == Some(server.port))
+            .filter(|url| server.has_state(url));
+
+        return match url {
+            Some(url) => Route::Redirect(url.to_string()),
+            None => Route::Ignored("400 Bad Request"),
+        };
     }
 
-    let script = format!(
-        r#"<script>fetch("http://{}:{}/cb",{{headers:{{"Full-Url":window.location.href}}}})</script>"#,
+    Route::Ignored("404 Not Found")
+}
+
+/// Script reporting the url back to the server, for providers sending the parameters in the fragment.
+fn callback_script(server: &Server, request: &Request) -> String {
+    let is_localhost = request
+        .host
+        .as_deref()
+        .map_or(false, |host| host.starts_with("localhost"));
+
+    format!(
+        r#"<script>if(location.hash){{fetch("http://{}:{}{}",{{headers:{{"{}":window.location.href}}}})}}</script>"#,
         if is_localhost {
             "localhost"
         } else {
             "127.0.0.1"
         },
-        port
-    );
-    let response = match response {
+        server.port,
+        server.callback_path(),
+        FULL_URL_HEADER
+    )
+}
+
+/// Whether the provider redirected with an error instead of the expected parameters.
+fn is_failure(url: &str) -> bool {
+    url::Url::parse(url).map_or(false, |url| {
+        url.query_pairs().any(|(key, _)| key == "error")
+    })
+}
+
+fn completion_page(server: &Server, script: Option<&str>, failed: bool) -> String {
+    let script = script.unwrap_or_default();
+
+    let response = match failed {
+        true => server.failure_response.as_deref().or(server.response.as_deref()),
+        false => server.response.as_deref(),
+    };
+
+    match response {
         Some(s) if s.contains("<head>") => s.replace("<head>", &format!("<head>{}", script)),
         Some(s) if s.contains("<body>") => {
             s.replace("<body>", &format!("<head>{}</head><body>", script))
@@ -160,37 +465,43 @@ fn handle_connection(mut conn: TcpStream, response: Option<&str>, port: u16) ->
             "<html><head>{}</head><body>Please return to the app.</body></html>",
             script
         ),
-    };
+    }
+}
 
-    // TODO: Test if unwrapping here is safe (enough).
-    conn.write_all(
-        format!(
-            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
-            response.len(),
-            response
-        )
-        .as_bytes(),
-    )
-    .unwrap();
-    conn.flush().unwrap();
+async fn write_response(conn: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
 
-    None
+    let written = match conn.write_all(response.as_bytes()).await {
+        Ok(_) => conn.flush().await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = written {
+        log::error!("Error sending the oauth response: {}", err);
+    }
 }
 
 /// Stops the currently running server behind the provided port without executing the handler.
-/// Alternatively you can send a request to http://127.0.0.1:port/exit
 ///
 /// # Errors
 ///
-/// - Returns `std::io::Error` if the server couldn't be reached.
-pub fn cancel(port: u16) -> Result<(), std::io::Error> {
-    // Using tcp instead of something global-ish like an AtomicBool,
-    // so we don't have to dive into the set_nonblocking madness.
-    let mut stream = TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], port)))?;
-    stream.write_all(&EXIT)?;
-    stream.flush()?;
-
-    Ok(())
+/// - Returns `std::io::Error` if no server is running behind the port.
+pub fn cancel(port: u16) -> Result<(), io::Error> {
+    match SERVERS.lock().unwrap().remove(&port) {
+        Some(token) => {
+            token.cancel();
+            Ok(())
+        }
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No oauth server is running on port {}", port),
+        )),
+    }
 }
 
 mod plugin_impl {
@@ -200,7 +511,7 @@ mod plugin_impl {
     pub(crate) fn start<R: Runtime>(
         window: Window<R>,
         config: Option<super::OauthConfig>,
-    ) -> Result<u16, String> {
+    ) -> Result<super::OauthSession, String> {
         let mut config = config.unwrap_or_default();
         if config.response.is_none() {
             config.response = window
@@ -246,3 +557,238 @@ pub fn init<R: Runtime>() -> TauriPlugin<R> {
         ])
         .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: &str = "s7aTe";
+
+    struct TestServer {
+        port: u16,
+        urls: mpsc::UnboundedReceiver<String>,
+    }
+
+    async fn spawn(read_timeout: Duration) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = Arc::new(Server {
+            port,
+            path: DEFAULT_PATH.to_string(),
+            state: Some(STATE.to_string()),
+            response: None,
+            failure_response: None,
+            read_timeout,
+        });
+
+        let (sender, urls) = mpsc::unbounded_channel();
+        tokio::spawn(serve(listener, server, CancellationToken::new(), move |url, completion: Completion| {
+            completion.complete(true);
+            sender.send(url).ok();
+        }));
+
+        TestServer { port, urls }
+    }
+
+    /// Sends the request in separate writes and returns the whole response.
+    async fn send(port: u16, chunks: &[&[u8]]) -> String {
+        let mut conn = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+        for chunk in chunks {
+            conn.write_all(chunk).await.unwrap();
+            conn.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let mut response = vec![];
+        conn.read_to_end(&mut response).await.ok();
+        String::from_utf8_lossy(&response).to_string()
+    }
+
+    fn redirect(port: u16) -> String {
+        format!("http://localhost:{}/?code=c0de&state={}", port, STATE)
+    }
+
+    async fn assert_redirect_handled(server: &mut TestServer) {
+        let request = format!("GET /?code=c0de&state={} HTTP/1.1\r\nHost: localhost\r\n\r\n", STATE);
+        let response = send(server.port, &[request.as_bytes()]).await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert_eq!(server.urls.recv().await.unwrap(), redirect(server.port));
+    }
+
+    #[tokio::test]
+    async fn reassembles_fragmented_requests() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        let response = send(
+            server.port,
+            &[b"GET /?code=c0de&st", b"ate=s7aTe HTTP/1.1\r\nHo", b"st: localhost\r\n", b"\r\n"],
+        )
+        .await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("Please return to the app."));
+        assert_eq!(server.urls.recv().await.unwrap(), redirect(server.port));
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_over_the_size_limit() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        let request = format!("GET /?code={} HTTP/1.1\r\n", "a".repeat(MAX_REQUEST_SIZE));
+        let response = send(server.port, &[request.as_bytes()]).await;
+
+        assert!(response.starts_with("HTTP/1.1 431"), "{}", response);
+        assert!(server.urls.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_over_the_header_limit() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        let headers = (0..=MAX_HEADERS)
+            .map(|i| format!("X-Header-{}: {}\r\n", i, i))
+            .collect::<String>();
+        let request = format!("GET /?code=c0de&state={} HTTP/1.1\r\n{}\r\n", STATE, headers);
+        let response = send(server.port, &[request.as_bytes()]).await;
+
+        assert!(response.starts_with("HTTP/1.1 431"), "{}", response);
+        assert!(server.urls.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn drops_clients_exceeding_the_read_timeout() {
+        let mut server = spawn(Duration::from_millis(100)).await;
+
+        let mut conn = TcpStream::connect(("127.0.0.1", server.port)).await.unwrap();
+        conn.write_all(b"GET /?code=c0de HTTP/1.1\r\n").await.unwrap();
+
+        let mut response = vec![];
+        let read = tokio::time::timeout(Duration::from_secs(5), conn.read_to_end(&mut response))
+            .await
+            .expect("the connection should have been closed");
+
+        assert_eq!(read.unwrap(), 0);
+        assert_redirect_handled(&mut server).await;
+    }
+
+    #[tokio::test]
+    async fn slow_clients_do_not_block_the_redirect() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        let _idle = TcpStream::connect(("127.0.0.1", server.port)).await.unwrap();
+
+        assert_redirect_handled(&mut server).await;
+    }
+
+    #[tokio::test]
+    async fn ignores_other_paths() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        let request = format!("GET /other?code=c0de&state={} HTTP/1.1\r\n\r\n", STATE);
+        let response = send(server.port, &[request.as_bytes()]).await;
+
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        assert!(server.urls.try_recv().is_err());
+        assert_redirect_handled(&mut server).await;
+    }
+
+    #[tokio::test]
+    async fn ignores_redirects_with_another_state() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        for target in ["/?code=c0de&state=other", "/?code=c0de"] {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            let response = send(server.port, &[request.as_bytes()]).await;
+
+            assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        }
+
+        assert!(server.urls.try_recv().is_err());
+        assert_redirect_handled(&mut server).await;
+    }
+
+    #[tokio::test]
+    async fn accepts_fragments_reported_by_the_page() {
+        let mut server = spawn(READ_TIMEOUT).await;
+
+        let response = send(server.port, &[b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"]).await;
+        assert!(response.contains(&format!("http://localhost:{}/cb", server.port)), "{}", response);
+
+        let url = format!("http://localhost:{}/#code=c0de&state={}", server.port, STATE);
+        let request = format!("GET /cb HTTP/1.1\r\n{}: {}\r\n\r\n", FULL_URL_HEADER, url);
+        let response = send(server.port, &[request.as_bytes()]).await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert_eq!(server.urls.recv().await.unwrap(), url);
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_the_server_without_calling_the_handler() {
+        let (sender, mut urls) = mpsc::unbounded_channel();
+        let config = OauthConfig {
+            state: Some(STATE.to_string()),
+            ..Default::default()
+        };
+
+        let session = start_with_config(config, move |url| {
+            sender.send(url).ok();
+        })
+        .unwrap();
+
+        assert_eq!(session.path, "/");
+        assert_eq!(session.redirect_uri, format!("http://localhost:{}/", session.port));
+
+        cancel(session.port).unwrap();
+        assert!(cancel(session.port).is_err());
+
+        // The handler is dropped along with the server, closing the channel.
+        assert!(urls.recv().await.is_none());
+        assert!(TcpStream::connect(("127.0.0.1", session.port)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn holds_the_page_until_the_login_completes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = Arc::new(Server {
+            port,
+            path: DEFAULT_PATH.to_string(),
+            state: None,
+            response: Some("<body>success</body>".into()),
+            failure_response: Some("<body>failure</body>".into()),
+            read_timeout: READ_TIMEOUT,
+        });
+
+        let (sender, mut completions) = mpsc::unbounded_channel();
+        tokio::spawn(serve(listener, server, CancellationToken::new(), move |_, completion| {
+            sender.send(completion).ok();
+        }));
+
+        let response = tokio::spawn(send(port, &[b"GET /?code=c0de HTTP/1.1\r\n\r\n"]));
+        let completion = completions.recv().await.unwrap();
+
+        // Nothing is sent while the code is being exchanged.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!response.is_finished());
+
+        completion.complete(false);
+        let response = response.await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("failure"));
+    }
+
+    #[test]
+    fn rejects_relative_paths() {
+        let config = OauthConfig {
+            path: Some("callback".into()),
+            ..Default::default()
+        };
+
+        assert!(start_with_config(config, |_| {}).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Loopback HTTP server standing in for the services we talk to in tests.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Request received by the server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap_or_default()
+    }
+}
+
+/// Status and body of a response.
+pub type Response = (u16, Vec<u8>);
+
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    /// Serves every request with `handler` until the runtime shuts down.
+    pub async fn start<H>(handler: H) -> TestServer
+    where
+        H: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let handler = Arc::new(handler);
+        let received = requests.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let received = received.clone();
+
+                tokio::spawn(async move {
+                    serve(stream, &*handler, &received).await;
+                });
+            }
+        });
+
+        TestServer { url, requests }
+    }
+
+    /// Requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+}
+
+pub fn json(status: u16, body: serde_json::Value) -> Response {
+    (status, body.to_string().into_bytes())
+}
+
+async fn serve<H>(mut stream: TcpStream, handler: &H, received: &Mutex<Vec<Request>>) -> Option<()>
+where
+    H: Fn(&Request) -> Response,
+{
+    let mut buffer = vec![];
+    let mut chunk = [0; 4096];
+
+    let (mut request, head_length, content_length) = loop {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut parsed = httparse::Request::new(&mut headers);
+
+        if let httparse::Status::Complete(length) = parsed.parse(&buffer).ok()? {
+            let content_length = parsed
+                .headers
+                .iter()
+                .find(|header| header.name.eq_ignore_ascii_case("content-length"))
+                .and_then(|header| std::str::from_utf8(header.value).ok())
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or(0);
+
+            let request = Request {
+                method: parsed.method?.to_string(),
+                path: parsed.path?.to_string(),
+                body: vec![],
+            };
+
+            break (request, length, content_length);
+        }
+    };
+
+    while buffer.len() < head_length + content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    request.body = buffer[head_length..head_length + content_length].to_vec();
+
+    let (status, body) = handler(&request);
+    received.lock().unwrap().push(request);
+    let head = format!(
+        "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nContent-Type: application/json\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await.ok()?;
+    stream.write_all(&body).await.ok()?;
+    stream.shutdown().await.ok();
+
+    Some(())
+}
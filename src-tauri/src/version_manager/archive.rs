@@ -0,0 +1,404 @@
+use crate::files::instance::{InstanceSettings, INSTANCE_SETTINGS_FILE};
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::instances::{Instance, Loader, PackSource};
+use crate::version_manager::version::VersionId;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Describes the instance inside the archive, next to its files.
+const DESCRIPTOR_FILE: &str = "cognatize-instance.json";
+const FILES_FOLDER: &str = "files";
+const ARCHIVE_FORMAT: u32 = 1;
+
+/// Which files of the instance directory end up in the archive.
+/// Patterns are relative to the instance directory, `*` matches within a single
+/// path segment and a pattern matching a directory matches everything inside it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportOptions {
+    /// Exported even when an exclude pattern matches them, e.g. `saves/My World`.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default = "default_exclude")]
+    pub exclude: Vec<String>,
+}
+
+fn default_exclude() -> Vec<String> {
+    ["saves", "logs", "crash-reports", "screenshots"]
+        .iter()
+        .map(|it| it.to_string())
+        .collect()
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            include: vec![],
+            exclude: default_exclude(),
+        }
+    }
+}
+
+impl ExportOptions {
+    fn exports(&self, path: &str) -> bool {
+        let matches = |pattern: &String| pattern_matches(pattern, path);
+
+        !self.exclude.iter().any(matches) || self.include.iter().any(matches)
+    }
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    let path = path.split('/').collect::<Vec<_>>();
+
+    if pattern.is_empty() || pattern.len() > path.len() {
+        return false;
+    }
+
+    pattern
+        .iter()
+        .zip(&path)
+        .all(|(pattern, segment)| segment_matches(pattern, segment))
+}
+
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == segment,
+        Some((prefix, rest)) => {
+            if !segment.starts_with(prefix) {
+                return false;
+            }
+
+            let remaining = &segment[prefix.len()..];
+            (0..=remaining.len())
+                .filter(|i| remaining.is_char_boundary(*i))
+                .any(|i| segment_matches(rest, &remaining[i..]))
+        }
+    }
+}
+
+/// Instance as written to the descriptor. The id is left out, the importer picks its own.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InstanceDescriptor {
+    pub format: u32,
+    pub name: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub version: VersionId,
+    #[serde(default)]
+    pub loader: Loader,
+    #[serde(default)]
+    pub source: PackSource,
+    #[serde(default)]
+    pub settings: InstanceSettings,
+}
+
+impl InstanceDescriptor {
+    pub fn into_instance(self, id: String) -> (Instance, InstanceSettings) {
+        let mut instance = Instance::new(id, self.name, self.version, self.loader, self.source);
+        instance.icon = self.icon;
+
+        (instance, self.settings)
+    }
+}
+
+/// Writes the instance directory, its settings and its loader to a zip archive at `to`.
+pub fn export(
+    instance: &Instance,
+    instance_path: &PathBuf,
+    settings: &InstanceSettings,
+    options: &ExportOptions,
+    to: &Path,
+) -> Result<(), ManagerError> {
+    let mut zip = ZipWriter::new(fs::File::create(to)?);
+    let file_options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let descriptor = InstanceDescriptor {
+        format: ARCHIVE_FORMAT,
+        name: instance.name.clone(),
+        icon: instance.icon.clone(),
+        version: instance.version.clone(),
+        loader: instance.loader.clone(),
+        source: instance.source.clone(),
+        settings: settings.clone(),
+    };
+
+    zip.start_file(DESCRIPTOR_FILE, file_options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&descriptor)?)?;
+
+    let mut files = vec![];
+    if instance_path.exists() {
+        collect_files(instance_path, "", options, &mut files)?;
+    }
+
+    for (name, path) in &files {
+        zip.start_file(format!("{}/{}", FILES_FOLDER, name), file_options)?;
+        io::copy(&mut fs::File::open(path)?, &mut zip)?;
+    }
+
+    zip.finish()?;
+
+    info!("Exported {} files of {} to {:?}", files.len(), instance.id, to);
+    Ok(())
+}
+
+fn collect_files(
+    dir: &PathBuf,
+    prefix: &str,
+    options: &ExportOptions,
+    files: &mut Vec<(String, PathBuf)>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = match entry.file_name().to_str() {
+            Some(name) => format!("{}{}", prefix, name),
+            None => continue,
+        };
+
+        // Already part of the descriptor
+        if name == INSTANCE_SETTINGS_FILE {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            // Included paths may live inside an excluded directory
+            collect_files(&entry.path(), &format!("{}/", name), options, files)?;
+        } else if file_type.is_file() && options.exports(&name) {
+            files.push((name, entry.path()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the descriptor of an archive made by [`export`].
+/// The settings able to run commands are dropped, archives being shared between users.
+pub fn read_descriptor(archive: &Path) -> Result<InstanceDescriptor, ManagerError> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+    let mut descriptor: InstanceDescriptor =
+        serde_json::from_reader(zip.by_name(DESCRIPTOR_FILE)?)?;
+
+    if descriptor.format > ARCHIVE_FORMAT {
+        return Err(ManagerError::UnsupportedArchive(format!(
+            "format {} is newer than {}",
+            descriptor.format, ARCHIVE_FORMAT
+        )));
+    }
+
+    descriptor.settings = descriptor.settings.without_commands();
+    descriptor
+        .settings
+        .validate()
+        .map_err(|err| ManagerError::UnsupportedArchive(err.to_string()))?;
+
+    Ok(descriptor)
+}
+
+/// Extracts the files of the archive to `to`, which must not exist yet.
+/// Nothing is left at `to` when the extraction fails.
+pub fn extract(archive: &Path, to: &PathBuf) -> Result<(), ManagerError> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+
+    fs::create_dir(to)?;
+
+    if let Err(err) = extract_folder(&mut zip, FILES_FOLDER, to) {
+        fs::remove_dir_all(to)?;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Whether the relative path stays inside the directory it is joined to.
+pub fn is_enclosed(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|it| matches!(it, Component::Normal(_)))
+}
+
+/// Extracts the entries inside `folder` of the archive to `to`, overwriting existing files.
+/// Returns how many files were extracted.
+pub fn extract_folder<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    folder: &str,
+    to: &Path,
+) -> Result<usize, ManagerError> {
+    let mut extracted = 0;
+
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+
+        // Entries outside of the folder, or escaping `to`, are skipped
+        let path = match file.enclosed_name().and_then(|it| it.strip_prefix(folder).ok()) {
+            Some(path) if is_enclosed(path) => to.join(path),
+            _ => continue,
+        };
+
+        if file.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        io::copy(&mut file, &mut fs::File::create(&path)?)?;
+        extracted += 1;
+    }
+
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn instance() -> Instance {
+        Instance::new(
+            "modded".to_string(),
+            "Modded".to_string(),
+            VersionId::new("1.20.1"),
+            Loader::Vanilla,
+            PackSource::Custom,
+        )
+    }
+
+    /// Archive with the given entries, as a hand-crafted or malicious one could contain.
+    fn archive_with(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+
+        for (name, contents) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn imports_what_was_exported() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        write(&source.join("options.txt"), "fov:90");
+        write(&source.join("mods/sodium.jar"), "sodium");
+        write(&source.join("saves/Old World/level.dat"), "old");
+        write(&source.join("saves/Keep/level.dat"), "keep");
+        write(&source.join("logs/latest.log"), "log");
+        write(&source.join(INSTANCE_SETTINGS_FILE), "{}");
+
+        let options = ExportOptions {
+            include: vec!["saves/Keep".to_string()],
+            exclude: vec!["saves".to_string(), "logs".to_string(), "*.txt".to_string()],
+        };
+        let settings = InstanceSettings {
+            max_memory: Some(4096),
+            ..InstanceSettings::default()
+        };
+        let archive = dir.path().join("modded.zip");
+        export(&instance(), &source, &settings, &options, &archive).unwrap();
+
+        let descriptor = read_descriptor(&archive).unwrap();
+        let target = dir.path().join("target");
+        extract(&archive, &target).unwrap();
+
+        assert_eq!(descriptor.name, "Modded");
+        assert_eq!(descriptor.version, VersionId::new("1.20.1"));
+        assert_eq!(descriptor.settings.max_memory, Some(4096));
+        assert_eq!(fs::read_to_string(target.join("mods/sodium.jar")).unwrap(), "sodium");
+        assert_eq!(fs::read_to_string(target.join("saves/Keep/level.dat")).unwrap(), "keep");
+        assert!(!target.join("saves/Old World").exists());
+        assert!(!target.join("logs").exists());
+        assert!(!target.join("options.txt").exists());
+        assert!(!target.join(INSTANCE_SETTINGS_FILE).exists());
+    }
+
+    #[test]
+    fn drops_the_commands_of_imported_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = InstanceSettings {
+            max_memory: Some(4096),
+            jvm_arguments: Some(vec!["-javaagent:evil.jar".to_string()]),
+            environment: HashMap::from([("LD_PRELOAD".to_string(), "evil.so".to_string())]),
+            wrapper: Some("sh -c evil".to_string()),
+            ..InstanceSettings::default()
+        };
+        let archive = dir.path().join("modded.zip");
+        let options = ExportOptions::default();
+        export(&instance(), &dir.path().join("none"), &settings, &options, &archive).unwrap();
+
+        let settings = read_descriptor(&archive).unwrap().settings;
+
+        assert_eq!(settings.max_memory, Some(4096));
+        assert!(settings.jvm_arguments.is_none());
+        assert!(settings.environment.is_empty());
+        assert!(settings.wrapper.is_none());
+    }
+
+    #[test]
+    fn refuses_invalid_imported_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("modded.zip");
+        let descriptor = r#"{
+            "format": 1,
+            "name": "Modded",
+            "version": "1.20.1",
+            "settings": {"min_memory": 4096, "max_memory": 1024}
+        }"#;
+        archive_with(&archive, &[(DESCRIPTOR_FILE, descriptor)]);
+
+        assert!(matches!(
+            read_descriptor(&archive),
+            Err(ManagerError::UnsupportedArchive(_))
+        ));
+    }
+
+    #[test]
+    fn skips_entries_escaping_the_instance() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("modded.zip");
+        archive_with(
+            &archive,
+            &[
+                ("files/../../escaped.txt", "escaped"),
+                ("files/mods/../../escaped.txt", "escaped"),
+                ("files/options.txt", "fov:90"),
+            ],
+        );
+
+        let target = dir.path().join("instances").join("modded");
+        fs::create_dir(dir.path().join("instances")).unwrap();
+        extract(&archive, &target).unwrap();
+
+        assert_eq!(fs::read_to_string(target.join("options.txt")).unwrap(), "fov:90");
+        assert!(!dir.path().join("escaped.txt").exists());
+        assert!(!dir.path().join("instances/escaped.txt").exists());
+    }
+
+    #[test]
+    fn removes_the_directory_when_extraction_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("modded.zip");
+        // The second entry needs a directory where the first one is a file
+        archive_with(&archive, &[("files/mods", "file"), ("files/mods/sodium.jar", "sodium")]);
+
+        let target = dir.path().join("modded");
+
+        assert!(extract(&archive, &target).is_err());
+        assert!(!target.exists());
+    }
+}
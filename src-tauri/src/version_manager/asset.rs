@@ -1,11 +1,17 @@
+use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::de::{Error, MapAccess};
 use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::iter::Map;
 use std::path::PathBuf;
 use std::str::FromStr;
+use crate::version_manager::version::VersionType;
+
+/// Repository of the libraries which don't tell where they are hosted.
+const MINECRAFT_LIBRARIES: &str = "https://libraries.minecraft.net/";
 
 // ROOT
 #[derive(Serialize, Deserialize, Debug)]
@@ -18,21 +24,81 @@ pub struct VersionManifest {
     pub assets: String,
     #[serde(rename = "mainClass")]
     pub main_class: String,
-    pub arguments: Arguments,
+    /// Only present on 1.13 and newer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Arguments>,
+    /// Game arguments of 1.12.2 and older, as a single string.
+    #[serde(
+        rename = "minecraftArguments",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub minecraft_arguments: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl VersionManifest {
+    /// Whether the game understands the Quick Play arguments, added in 1.20.
+    pub fn supports_quick_play(&self) -> bool {
+        self.has_feature_argument("is_quick_play_multiplayer")
+    }
+
+    /// Whether the game arguments ask for the resolution, which legacy versions don't.
+    pub fn supports_custom_resolution(&self) -> bool {
+        self.has_feature_argument("has_custom_resolution")
+    }
+
+    fn has_feature_argument(&self, feature: &str) -> bool {
+        self.game_arguments().iter().any(|arg| match arg {
+            Argument::WithRules { rules, .. } => rules.iter().any(|rule| {
+                rule.features
+                    .as_ref()
+                    .map_or(false, |it| it.contains_key(feature))
+            }),
+            Argument::Plain(_) => false,
+        })
+    }
+
+    /// Legacy manifests don't list JVM arguments, so we use the ones the vanilla launcher added.
+    pub fn jvm_arguments(&self) -> Vec<Argument> {
+        match &self.arguments {
+            Some(arguments) => arguments.jvm.clone(),
+            None => ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}"]
+                .iter()
+                .map(|arg| Argument::Plain(arg.to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn game_arguments(&self) -> Vec<Argument> {
+        match (&self.arguments, &self.minecraft_arguments) {
+            (Some(arguments), _) => arguments.game.clone(),
+            (None, Some(arguments)) => arguments
+                .split_whitespace()
+                .map(|arg| Argument::Plain(arg.to_string()))
+                .collect(),
+            (None, None) => vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Arguments {
+    /// Loader profiles may only list one of them.
+    #[serde(default)]
     pub game: Vec<Argument>,
+    #[serde(default)]
     pub jvm: Vec<Argument>,
 }
 
-#[derive(Serialize, Debug)]
+/// Written back the way manifests list it, so cached loader profiles can be read again.
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
 pub enum Argument {
     Plain(String),
+    /// The rules come first, as the deserializer expects.
     WithRules {
-        value: ArgumentValue,
         rules: Vec<Rule>,
+        value: ArgumentValue,
     },
 }
 
@@ -44,7 +110,8 @@ impl FromStr for Argument {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
 pub enum ArgumentValue {
     String(String),
     Array(Vec<String>),
@@ -155,60 +222,296 @@ impl Artifact {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Os {
     pub name: Option<String>,
     pub version: Option<String>,
     pub arch: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Os {
+    /// Every present field has to match the system.
+    fn matches(&self, system: &System) -> bool {
+        let name_matches = self.name.as_ref().map_or(true, |name| name == system.name);
+        let arch_matches = self.arch.as_ref().map_or(true, |arch| arch == system.arch);
+        let version_matches = self.version.as_ref().map_or(true, |version| {
+            // An invalid pattern can't match, the same way vanilla handles it.
+            Regex::new(version).map_or(false, |regex| regex.is_match(&system.version))
+        });
+
+        name_matches && arch_matches && version_matches
+    }
+}
+
+/// System the OS rules are evaluated against.
+#[derive(Debug, Clone)]
+pub struct System {
+    pub name: &'static str,
+    pub arch: &'static str,
+    pub version: String,
+}
+
+static CURRENT_SYSTEM: Lazy<System> = Lazy::new(|| System {
+    name: current_os(),
+    arch: current_arch(),
+    version: os_info::get().version().to_string(),
+});
+
+/// Name of the current OS as written in the manifests.
+fn current_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "osx",
+        os => os,
+    }
+}
+
+/// Architecture of the current system as written in the manifests,
+/// where `x86` only stands for 32 bits.
+fn current_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86" => "x86",
+        "x86_64" => "x86_64",
+        "aarch64" => "arm64",
+        "arm" => "arm32",
+        arch => arch,
+    }
+}
+
+/// Stands for `${arch}` in native classifiers, which only tell 32 from 64 bits.
+fn pointer_width() -> &'static str {
+    match cfg!(target_pointer_width = "64") {
+        true => "64",
+        false => "32",
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Allow,
+    Disallow,
+}
+
+/// Optional launcher features some arguments depend on.
+#[derive(Debug, Clone, Default)]
+pub struct Features {
+    pub is_demo_user: bool,
+    pub has_custom_resolution: bool,
+    pub has_quick_plays_support: bool,
+    pub is_quick_play_singleplayer: bool,
+    pub is_quick_play_multiplayer: bool,
+    pub is_quick_play_realms: bool,
+}
+
+impl Features {
+    fn enabled(&self, feature: &str) -> bool {
+        match feature {
+            "is_demo_user" => self.is_demo_user,
+            "has_custom_resolution" => self.has_custom_resolution,
+            "has_quick_plays_support" => self.has_quick_plays_support,
+            "is_quick_play_singleplayer" => self.is_quick_play_singleplayer,
+            "is_quick_play_multiplayer" => self.is_quick_play_multiplayer,
+            "is_quick_play_realms" => self.is_quick_play_realms,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Rule {
+    pub action: RuleAction,
     pub os: Option<Os>,
-    pub action: String,
+    pub features: Option<HashMap<String, bool>>,
 }
 
 impl Rule {
-    pub fn is_valid(&self) -> bool {
-        if let Some(os) = &self.os {
-            if let Some(name) = &os.name {
-                let current_os = std::env::consts::OS;
-                name == current_os && self.action == "allow"
-            } else if let Some(arch) = &os.arch {
-                let current_arch = std::env::consts::ARCH;
-                arch == current_arch && self.action == "allow"
-            } else if let Some(ver) = &os.version {
-                let regex = Regex::new(ver).expect("Failed to compile version regex");
-                let version = os_info::get().version().to_string();
-                regex.is_match(&version) && self.action == "allow"
-            } else {
-                false
-            }
-        } else {
-            false
+    /// Whether the rule applies to the current system with the given features.
+    pub fn matches(&self, features: &Features) -> bool {
+        self.matches_on(features, &CURRENT_SYSTEM)
+    }
+
+    fn matches_on(&self, features: &Features, system: &System) -> bool {
+        let os_matches = self.os.as_ref().map_or(true, |os| os.matches(system));
+        let features_match = self.features.as_ref().map_or(true, |required| {
+            required
+                .iter()
+                .all(|(feature, value)| features.enabled(feature) == *value)
+        });
+
+        os_matches && features_match
+    }
+
+    /// Evaluates the rules the way vanilla does: when there are rules nothing is
+    /// allowed by default, and the last rule matching decides.
+    pub fn allows(rules: &[Rule], features: &Features) -> bool {
+        Rule::allows_on(rules, features, &CURRENT_SYSTEM)
+    }
+
+    fn allows_on(rules: &[Rule], features: &Features, system: &System) -> bool {
+        if rules.is_empty() {
+            return true;
         }
+
+        rules.iter().fold(false, |allowed, rule| match rule.matches_on(features, system) {
+            true => rule.action == RuleAction::Allow,
+            false => allowed,
+        })
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Library {
     pub name: String,
+    /// Loader profiles often leave it out, the artifact is then found by its name.
+    #[serde(default)]
     pub downloads: LibraryDownloads,
+    /// Maven repository hosting the library when there are no downloads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
     pub rules: Option<Vec<Rule>>,
+    /// Classifier holding the native code for each OS, e.g. `natives-windows-${arch}`.
+    /// Only used by legacy versions, newer ones list natives as regular libraries.
+    pub natives: Option<HashMap<String, String>>,
+    /// How the natives are extracted to the natives directory.
+    pub extract: Option<ExtractRules>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Library {
+    pub fn is_allowed(&self, features: &Features) -> bool {
+        self.rules
+            .as_ref()
+            .map_or(true, |rules| Rule::allows(rules, features))
+    }
+
+    /// Artifact going on the classpath. Libraries without downloads are looked up by
+    /// their name in their repository, except the native only ones of legacy versions.
+    pub fn artifact(&self) -> Option<Artifact> {
+        if let Some(artifact) = &self.downloads.artifact {
+            return Some(artifact.clone());
+        }
+
+        if self.natives.is_some() || self.downloads.classifiers.is_some() {
+            return None;
+        }
+
+        let path = maven_path(&self.name)?;
+        let repository = self.url.as_deref().unwrap_or(MINECRAFT_LIBRARIES);
+
+        Some(Artifact {
+            id: Some(self.name.clone()),
+            url: format!("{}/{}", repository.trim_end_matches('/'), path),
+            path: Some(path),
+            // Unknown checksums are left empty, only the presence of the file is checked
+            sha1: self.sha1.clone().unwrap_or_default(),
+            size: self.size.unwrap_or_default(),
+        })
+    }
+
+    /// Identifies the library regardless of its version, e.g. `org.ow2.asm:asm`.
+    pub fn key(&self) -> String {
+        let coordinate = self.name.split('@').next().unwrap_or_default();
+        let parts = coordinate.split(':').collect::<Vec<_>>();
+
+        match parts[..] {
+            [group, artifact, _, classifier] => format!("{}:{}:{}", group, artifact, classifier),
+            [group, artifact, ..] => format!("{}:{}", group, artifact),
+            _ => coordinate.to_string(),
+        }
+    }
+
+    /// Artifact holding the native code of the library for the current system, if any.
+    pub fn native_artifact(&self) -> Option<&Artifact> {
+        let classifier = self
+            .natives
+            .as_ref()?
+            .get(current_os())?
+            .replace("${arch}", pointer_width());
+
+        self.downloads.classifiers.as_ref()?.get(&classifier)
+    }
+
+    /// Whether the entry of the natives jar is left out when extracting it.
+    pub fn excludes(&self, entry: &str) -> bool {
+        self.extract.as_ref().map_or(false, |extract| {
+            extract.exclude.iter().any(|prefix| entry.starts_with(prefix.as_str()))
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExtractRules {
+    /// Prefixes of the entries to leave out, e.g. `META-INF/`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Path of a maven coordinate inside its repository, where
+/// `group:artifact:version[:classifier][@extension]` gives
+/// `group/artifact/version/artifact-version[-classifier].extension`.
+pub fn maven_path(coordinate: &str) -> Option<String> {
+    let (coordinate, extension) = coordinate.split_once('@').unwrap_or((coordinate, "jar"));
+    let parts = coordinate.split(':').collect::<Vec<_>>();
+
+    let (group, artifact, version, classifier) = match parts[..] {
+        [group, artifact, version] => (group, artifact, version, None),
+        [group, artifact, version, classifier] => (group, artifact, version, Some(classifier)),
+        _ => return None,
+    };
+
+    let file = match classifier {
+        Some(classifier) => format!("{}-{}-{}.{}", artifact, version, classifier, extension),
+        None => format!("{}-{}.{}", artifact, version, extension),
+    };
+
+    Some(format!("{}/{}/{}/{}", group.replace('.', "/"), artifact, version, file))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct LibraryDownloads {
-    pub artifact: Artifact,
+    /// Missing on native only libraries of legacy versions, which only list classifiers.
+    pub artifact: Option<Artifact>,
+    /// Artifacts by classifier, e.g. `natives-linux`.
+    pub classifiers: Option<HashMap<String, Artifact>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AssetObject {
+    pub hash: String,
+    pub size: u64,
+}
+
+impl AssetObject {
+    /// Path inside the object store, e.g. `ab/abcdef...`.
+    pub fn path(&self) -> String {
+        format!("{}/{}", &self.hash[..2], self.hash)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AssetIndex {
+    pub objects: HashMap<String, AssetObject>,
+    /// Legacy indexes (1.6 to 1.7.2) expect the objects by name in `assets/virtual/<index>`.
+    #[serde(default, rename = "virtual")]
+    pub is_virtual: bool,
+    /// Pre 1.6 indexes expect the objects by name in `<instance>/resources`.
+    #[serde(default)]
+    pub map_to_resources: bool,
 }
 
 // VERSIONS
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Version {
     pub id: String,
-    pub r#type: String,
+    pub r#type: VersionType,
     pub url: String,
+    /// RFC 3339 timestamps, all in UTC so they sort as strings.
+    #[serde(rename = "releaseTime")]
+    pub release_time: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -222,3 +525,283 @@ pub struct VersionsManifest {
     pub latest: VersionLatest,
     pub versions: Vec<Version>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Natives of LWJGL 2 as listed by the 1.12.2 manifest.
+    const LWJGL_PLATFORM: &str = r#"{
+        "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+        "downloads": {
+            "classifiers": {
+                "natives-linux": {
+                    "path": "org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-linux.jar",
+                    "sha1": "931074f46c795d2f7b30ed6395df5715cfd7675b",
+                    "size": 578680,
+                    "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-linux.jar"
+                },
+                "natives-osx": {
+                    "path": "org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-osx.jar",
+                    "sha1": "bcab850f8f487c3f4c4dbabde778bb82bd1a40ed",
+                    "size": 426822,
+                    "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-osx.jar"
+                },
+                "natives-windows": {
+                    "path": "org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-windows.jar",
+                    "sha1": "b84d5102b9dbfabfeb5e43c7e2828d98a7fc80e0",
+                    "size": 613748,
+                    "url": "https://libraries.minecraft.net/org/lwjgl/lwjgl/lwjgl-platform/2.9.4-nightly-20150209/lwjgl-platform-2.9.4-nightly-20150209-natives-windows.jar"
+                }
+            }
+        },
+        "extract": {
+            "exclude": ["META-INF/"]
+        },
+        "natives": {
+            "linux": "natives-linux",
+            "osx": "natives-osx",
+            "windows": "natives-windows"
+        }
+    }"#;
+
+    /// Shaped like the Twitch natives of 1.8.9, whose classifiers depend on the architecture.
+    const TWITCH_PLATFORM: &str = r#"{
+        "name": "tv.twitch:twitch-platform:6.5",
+        "downloads": {
+            "classifiers": {
+                "natives-osx": {
+                    "path": "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-osx.jar",
+                    "sha1": "5f9d1ee26257b3a33f0ca06fed335ef462af659f",
+                    "size": 455359,
+                    "url": "https://libraries.minecraft.net/tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-osx.jar"
+                },
+                "natives-windows-32": {
+                    "path": "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-32.jar",
+                    "sha1": "206c4ccaecdbcfd2a1631150c69a97bbc9c20c11",
+                    "size": 474225,
+                    "url": "https://libraries.minecraft.net/tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-32.jar"
+                },
+                "natives-windows-64": {
+                    "path": "tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-64.jar",
+                    "sha1": "9fdd0fd5aed0817063dcf95b69349a171f447ebd",
+                    "size": 580098,
+                    "url": "https://libraries.minecraft.net/tv/twitch/twitch-platform/6.5/twitch-platform-6.5-natives-windows-64.jar"
+                }
+            }
+        },
+        "natives": {
+            "osx": "natives-osx",
+            "windows": "natives-windows-${arch}"
+        }
+    }"#;
+
+    fn library(json: &str) -> Library {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn picks_the_classifier_of_the_current_os() {
+        let lib = library(LWJGL_PLATFORM);
+        let artifact = lib.native_artifact().expect("every OS has natives");
+
+        assert!(lib.downloads.artifact.is_none());
+        assert!(artifact
+            .url
+            .ends_with(&format!("-natives-{}.jar", current_os())));
+    }
+
+    #[test]
+    fn replaces_the_arch_of_the_classifier() {
+        let lib = library(TWITCH_PLATFORM);
+        let artifact = lib.native_artifact();
+
+        match current_os() {
+            "windows" => assert!(artifact
+                .unwrap()
+                .url
+                .ends_with(&format!("-natives-windows-{}.jar", pointer_width()))),
+            "osx" => assert!(artifact.unwrap().url.ends_with("-natives-osx.jar")),
+            _ => assert!(artifact.is_none()),
+        }
+    }
+
+    #[test]
+    fn excludes_entries_by_prefix() {
+        let lib = library(LWJGL_PLATFORM);
+
+        assert!(lib.excludes("META-INF/MANIFEST.MF"));
+        assert!(!lib.excludes("liblwjgl64.so"));
+        assert!(!library(TWITCH_PLATFORM).excludes("META-INF/MANIFEST.MF"));
+    }
+
+    #[test]
+    fn builds_maven_paths() {
+        let cases = [
+            (
+                "net.fabricmc:fabric-loader:0.14.21",
+                Some("net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar"),
+            ),
+            (
+                "net.minecraftforge:forge:1.20.1-47.2.0:client",
+                Some("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-client.jar"),
+            ),
+            (
+                "de.oceanlabs.mcp:mcp_config:1.20.1@zip",
+                Some("de/oceanlabs/mcp/mcp_config/1.20.1/mcp_config-1.20.1.zip"),
+            ),
+            (
+                "net.minecraft:client:1.20.1:mappings@txt",
+                Some("net/minecraft/client/1.20.1/client-1.20.1-mappings.txt"),
+            ),
+            ("not-a-coordinate", None),
+        ];
+
+        for (coordinate, path) in cases.iter() {
+            assert_eq!(maven_path(coordinate).as_deref(), *path, "{}", coordinate);
+        }
+    }
+
+    #[test]
+    fn finds_the_artifact_of_maven_libraries() {
+        let lib = library(r#"{
+            "name": "net.fabricmc:intermediary:1.20.1",
+            "url": "https://maven.fabricmc.net/",
+            "sha1": "0a7a7a4b5cf2ba5e1cd8f7a9e03b2a5f52ba6adc",
+            "size": 135000
+        }"#);
+        let artifact = lib.artifact().unwrap();
+
+        let path = "net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar";
+
+        assert_eq!(artifact.url, format!("https://maven.fabricmc.net/{}", path));
+        assert_eq!(artifact.path.as_deref(), Some(path));
+        assert_eq!(artifact.size, 135000);
+
+        let legacy = library(r#"{"name": "net.minecraft:launchwrapper:1.12"}"#).artifact().unwrap();
+        assert_eq!(
+            legacy.url,
+            "https://libraries.minecraft.net/net/minecraft/launchwrapper/1.12/launchwrapper-1.12.jar"
+        );
+        assert!(legacy.sha1.is_empty());
+
+        assert!(library(LWJGL_PLATFORM).artifact().is_none());
+    }
+
+    #[test]
+    fn identifies_libraries_regardless_of_their_version() {
+        let asm = library(r#"{"name": "org.ow2.asm:asm:9.5"}"#);
+        let natives = library(r#"{"name": "org.lwjgl:lwjgl:3.3.1:natives-linux"}"#);
+
+        assert_eq!(asm.key(), "org.ow2.asm:asm");
+        assert_eq!(natives.key(), "org.lwjgl:lwjgl:natives-linux");
+    }
+
+    fn rules(json: &str) -> Vec<Rule> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn system(name: &'static str, arch: &'static str, version: &str) -> System {
+        System {
+            name,
+            arch,
+            version: version.to_string(),
+        }
+    }
+
+    /// Rules of the LWJGL 3 libraries of 1.13, shipped for every OS but macOS.
+    const ALL_BUT_OSX: &str = r#"[
+        {"action": "allow"},
+        {"action": "disallow", "os": {"name": "osx"}}
+    ]"#;
+
+    /// Rule of the `-Dos.name=Windows 10` arguments of 1.13.
+    const WINDOWS_10: &str = r#"[
+        {"action": "allow", "os": {"name": "windows", "version": "^10\\."}}
+    ]"#;
+
+    /// Rule of the `-Xss1M` argument of 1.13.
+    const X86: &str = r#"[
+        {"action": "allow", "os": {"arch": "x86"}}
+    ]"#;
+
+    /// Rules of the resolution and demo arguments of 1.13.
+    const CUSTOM_RESOLUTION: &str = r#"[
+        {"action": "allow", "features": {"has_custom_resolution": true}}
+    ]"#;
+    const DEMO_USER: &str = r#"[
+        {"action": "allow", "features": {"is_demo_user": true}}
+    ]"#;
+
+    /// Rules of the LWJGL 2 nightlies of 1.12.2, only shipped for macOS.
+    const ONLY_OSX: &str = r#"[
+        {"action": "allow", "os": {"name": "osx"}}
+    ]"#;
+
+    const OSX_THEN_ALL: &str = r#"[
+        {"action": "disallow", "os": {"name": "osx"}},
+        {"action": "allow"}
+    ]"#;
+
+    const INVALID_VERSION: &str = r#"[
+        {"action": "allow", "os": {"version": "^10\\.("}}
+    ]"#;
+
+    #[test]
+    fn evaluates_os_rules() {
+        let cases = [
+            (ALL_BUT_OSX, system("linux", "x86_64", "6.1.0"), true),
+            (ALL_BUT_OSX, system("windows", "x86_64", "10.0.19045"), true),
+            (ALL_BUT_OSX, system("osx", "arm64", "14.1.0"), false),
+            (WINDOWS_10, system("windows", "x86_64", "10.0.19045"), true),
+            (WINDOWS_10, system("windows", "x86_64", "6.1.7601"), false),
+            (WINDOWS_10, system("linux", "x86_64", "10.4.0"), false),
+            (X86, system("windows", "x86", "10.0.19045"), true),
+            (X86, system("windows", "x86_64", "10.0.19045"), false),
+            (ONLY_OSX, system("osx", "x86_64", "10.15.7"), true),
+            (ONLY_OSX, system("linux", "x86_64", "6.1.0"), false),
+            (OSX_THEN_ALL, system("osx", "x86_64", "10.15.7"), true),
+            (INVALID_VERSION, system("windows", "x86_64", "10.0.19045"), false),
+            ("[]", system("linux", "x86_64", "6.1.0"), true),
+        ];
+
+        for (json, system, allowed) in cases.iter() {
+            assert_eq!(
+                Rule::allows_on(&rules(json), &Features::default(), system),
+                *allowed,
+                "{} on {:?}",
+                json,
+                system
+            );
+        }
+    }
+
+    #[test]
+    fn evaluates_feature_rules() {
+        let resolution = Features {
+            has_custom_resolution: true,
+            ..Features::default()
+        };
+        let demo = Features {
+            is_demo_user: true,
+            ..Features::default()
+        };
+        let cases = [
+            (CUSTOM_RESOLUTION, &resolution, true),
+            (CUSTOM_RESOLUTION, &demo, false),
+            (DEMO_USER, &demo, true),
+            (DEMO_USER, &resolution, false),
+        ];
+        let linux = system("linux", "x86_64", "6.1.0");
+
+        for (json, features, allowed) in cases.iter() {
+            assert_eq!(
+                Rule::allows_on(&rules(json), features, &linux),
+                *allowed,
+                "{} with {:?}",
+                json,
+                features
+            );
+        }
+    }
+}
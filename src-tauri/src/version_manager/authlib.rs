@@ -0,0 +1,206 @@
+use crate::auth_route::yggdrasil::{YggdrasilClient, YggdrasilServer};
+use crate::version_manager::errors::ManagerError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::{fs, io};
+use tracing::info;
+
+/// Release launched with Yggdrasil accounts, the agent runs with access to the
+/// game and the session, so it is pinned instead of following the latest release.
+const AUTHLIB_INJECTOR: InjectorArtifact<'static> = InjectorArtifact {
+    version: "1.2.5",
+    download_url: "https://github.com/yushijinhun/authlib-injector/releases/download/v1.2.5/authlib-injector-1.2.5.jar",
+    sha256: "3bc9ebdc583b36abd2a65b626c4b9f35f21177fbf42a851606eaaea3fd42ee0f",
+};
+
+#[derive(Clone, Debug)]
+struct InjectorArtifact<'a> {
+    version: &'a str,
+    download_url: &'a str,
+    sha256: &'a str,
+}
+
+/// Java agent redirecting the authentication of the game to a Yggdrasil server.
+pub struct AuthlibInjector {
+    jar: PathBuf,
+    api_url: String,
+    metadata: String,
+}
+
+impl AuthlibInjector {
+    /// Makes sure the agent is downloaded in `at` and prefetches the server metadata,
+    /// which spares the game a request to the server on startup.
+    pub async fn prepare(at: &PathBuf, server: &YggdrasilServer) -> Result<Self, ManagerError> {
+        Self::prepare_with(at, &AUTHLIB_INJECTOR, server).await
+    }
+
+    async fn prepare_with(
+        at: &PathBuf,
+        artifact: &InjectorArtifact<'_>,
+        server: &YggdrasilServer,
+    ) -> Result<Self, ManagerError> {
+        fs::create_dir_all(at)?;
+
+        let jar = Self::download(at, artifact).await?;
+        let metadata = YggdrasilClient::new(&server.url)?.metadata().await?;
+
+        Ok(Self {
+            jar,
+            api_url: server.url.clone(),
+            metadata,
+        })
+    }
+
+    /// Downloads the artifact unless a copy matching its checksum is already there,
+    /// so launching doesn't need the network once it has been downloaded.
+    async fn download(at: &PathBuf, artifact: &InjectorArtifact<'_>) -> Result<PathBuf, ManagerError> {
+        let jar = at.join(format!("authlib-injector-{}.jar", artifact.version));
+
+        if jar.exists() && Self::sha256(&jar)? == artifact.sha256 {
+            return Ok(jar);
+        }
+
+        info!("Downloading authlib-injector {}", artifact.version);
+        let bytes = reqwest::get(artifact.download_url)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        let hash = format!("{:x}", Sha256::digest(&bytes));
+        if hash != artifact.sha256 {
+            return Err(ManagerError::ChecksumMismatch(format!(
+                "authlib-injector {}",
+                artifact.version
+            )));
+        }
+
+        fs::write(&jar, &bytes)?;
+
+        Ok(jar)
+    }
+
+    fn sha256(path: &PathBuf) -> Result<String, ManagerError> {
+        let mut file = fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+
+        let _ = io::copy(&mut file, &mut hasher)?;
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn jvm_arguments(&self) -> Vec<String> {
+        vec![
+            format!("-javaagent:{}={}", self.jar.to_str().unwrap(), self.api_url),
+            format!(
+                "-Dauthlibinjector.yggdrasil.prefetched={}",
+                STANDARD.encode(&self.metadata)
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestServer;
+
+    const JAR: &[u8] = b"authlib-injector";
+    const METADATA: &str = r#"{"meta":{"serverName":"Test Server"},"skinDomains":[]}"#;
+
+    async fn stand_in() -> TestServer {
+        TestServer::start(|request| match request.path.as_str() {
+            "/authlib-injector.jar" => (200, JAR.to_vec()),
+            "/" => (200, METADATA.as_bytes().to_vec()),
+            _ => (404, vec![]),
+        })
+        .await
+    }
+
+    fn artifact<'a>(url: &'a str, sha256: &'a str) -> InjectorArtifact<'a> {
+        InjectorArtifact {
+            version: "test",
+            download_url: url,
+            sha256,
+        }
+    }
+
+    fn server(server: &TestServer) -> YggdrasilServer {
+        YggdrasilServer {
+            url: server.url.clone(),
+            name: None,
+            client_token: "client".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_the_pinned_artifact() {
+        let dir = tempfile::tempdir().unwrap();
+        let at = dir.path().to_path_buf();
+        let stand_in = stand_in().await;
+        let sha256 = format!("{:x}", Sha256::digest(JAR));
+        let url = stand_in.url("/authlib-injector.jar");
+        let artifact = artifact(&url, &sha256);
+
+        let injector = AuthlibInjector::prepare_with(&at, &artifact, &server(&stand_in))
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read(&injector.jar).unwrap(), JAR);
+        assert_eq!(injector.metadata, METADATA);
+
+        let arguments = injector.jvm_arguments();
+        assert!(arguments[0].starts_with("-javaagent:"));
+        assert!(arguments[0].ends_with(&format!("={}", stand_in.url)));
+        assert_eq!(
+            arguments[1],
+            format!("-Dauthlibinjector.yggdrasil.prefetched={}", STANDARD.encode(METADATA))
+        );
+
+        // The jar is reused as long as it matches the checksum.
+        AuthlibInjector::download(&at, &artifact).await.unwrap();
+        let downloads = stand_in
+            .requests()
+            .iter()
+            .filter(|request| request.path == "/authlib-injector.jar")
+            .count();
+        assert_eq!(downloads, 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_artifacts_not_matching_the_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let at = dir.path().to_path_buf();
+        let stand_in = stand_in().await;
+        let url = stand_in.url("/authlib-injector.jar");
+        let artifact = artifact(&url, "00");
+
+        let result = AuthlibInjector::download(&at, &artifact).await;
+
+        assert!(matches!(result, Err(ManagerError::ChecksumMismatch(_))));
+        assert_eq!(fs::read_dir(&at).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_artifact_is_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let at = dir.path().to_path_buf();
+        let stand_in = stand_in().await;
+        let url = stand_in.url("/missing.jar");
+        let artifact = artifact(&url, "00");
+
+        let result = AuthlibInjector::download(&at, &artifact).await;
+
+        assert!(matches!(result, Err(ManagerError::DownloadError(_))));
+    }
+
+    #[test]
+    fn pinned_artifact_is_consistent() {
+        assert!(AUTHLIB_INJECTOR
+            .download_url
+            .ends_with(&format!("/authlib-injector-{}.jar", AUTHLIB_INJECTOR.version)));
+        assert_eq!(AUTHLIB_INJECTOR.sha256.len(), 64);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::version_manager::archive;
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::instances::Loader;
+use crate::version_manager::version::VersionId;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use zip::ZipArchive;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DEFAULT_OVERRIDES_FOLDER: &str = "overrides";
+const CONCURRENT_DOWNLOADS: usize = 8;
+const SHA1_ALGORITHM: u32 = 1;
+const RESOURCE_PACK_CLASS: u32 = 12;
+const SHADER_PACK_CLASS: u32 = 6552;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ModLoader {
+    /// Loader and its version, e.g. `forge-47.1.0`.
+    pub id: String,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestMinecraft {
+    pub version: String,
+    #[serde(default)]
+    pub mod_loaders: Vec<ModLoader>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ManifestFile {
+    #[serde(rename = "projectID")]
+    pub project_id: u32,
+    #[serde(rename = "fileID")]
+    pub file_id: u32,
+    /// Disabled mods are exported as not required.
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Contents of `manifest.json` of a CurseForge pack export.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseManifest {
+    pub minecraft: ManifestMinecraft,
+    pub manifest_type: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    pub files: Vec<ManifestFile>,
+    #[serde(default)]
+    pub overrides: Option<String>,
+}
+
+impl CurseManifest {
+    pub fn read(archive: &Path) -> Result<CurseManifest, ManagerError> {
+        let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+        let manifest: CurseManifest = serde_json::from_reader(zip.by_name(MANIFEST_FILE)?)?;
+
+        if manifest.manifest_type != "minecraftModpack" {
+            return Err(ManagerError::UnsupportedArchive(format!(
+                "manifest type {}",
+                manifest.manifest_type
+            )));
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn minecraft_version(&self) -> VersionId {
+        VersionId::new(&self.minecraft.version)
+    }
+
+    pub fn loader(&self) -> Result<Loader, ManagerError> {
+        let loaders = &self.minecraft.mod_loaders;
+        let loader = match loaders.iter().find(|it| it.primary).or_else(|| loaders.first()) {
+            Some(loader) => loader,
+            None => return Ok(Loader::Vanilla),
+        };
+
+        let (name, version) = loader.id.split_once('-').ok_or_else(|| {
+            ManagerError::UnsupportedArchive(format!("mod loader {}", loader.id))
+        })?;
+        let version = version.to_string();
+
+        match name {
+            "forge" => Ok(Loader::Forge { version }),
+            "neoforge" => Ok(Loader::NeoForge { version }),
+            "fabric" => Ok(Loader::Fabric { version }),
+            "quilt" => Ok(Loader::Quilt { version }),
+            _ => Err(ManagerError::UnsupportedArchive(format!("mod loader {}", loader.id))),
+        }
+    }
+
+    fn required_files(&self) -> impl Iterator<Item = &ManifestFile> {
+        self.files.iter().filter(|file| file.required)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct FileHash {
+    value: String,
+    algo: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiFile {
+    id: u32,
+    mod_id: u32,
+    file_name: String,
+    /// Missing when the author doesn't allow third party downloads.
+    download_url: Option<String>,
+    #[serde(default)]
+    hashes: Vec<FileHash>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiLinks {
+    website_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiMod {
+    id: u32,
+    name: String,
+    class_id: Option<u32>,
+    links: ApiLinks,
+}
+
+/// File the user has to download from the CurseForge website and place in `path` by hand.
+#[derive(Serialize, Clone, Debug)]
+pub struct ManualDownload {
+    pub project_id: u32,
+    pub file_id: u32,
+    pub name: String,
+    pub file_name: String,
+    /// Path relative to the instance directory.
+    pub path: String,
+    pub url: Option<String>,
+}
+
+struct ResolvedFile {
+    url: String,
+    file_name: String,
+    destination: PathBuf,
+    sha1: Option<String>,
+}
+
+/// Client of the CurseForge API, which only answers requests bearing an API key.
+pub struct CurseClient {
+    http: reqwest::Client,
+    api_url: String,
+    api_key: String,
+}
+
+impl CurseClient {
+    pub fn new(api_url: &str, api_key: Option<&str>) -> Result<CurseClient, ManagerError> {
+        let api_key = api_key
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| ManagerError::CurseForgeError("no API key set".to_string()))?;
+
+        Ok(CurseClient {
+            http: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+        })
+    }
+
+    async fn post<T>(&self, path: &str, body: serde_json::Value) -> Result<T, ManagerError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let response = self
+            .http
+            .post(format!("{}{}", self.api_url, path))
+            .header("x-api-key", &self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(ManagerError::CurseForgeError(format!(
+                "{} answered {}",
+                path,
+                response.status()
+            )));
+        }
+
+        Ok(response.json::<ApiResponse<T>>().await?.data)
+    }
+
+    async fn files(&self, ids: Vec<u32>) -> Result<Vec<ApiFile>, ManagerError> {
+        self.post("/v1/mods/files", serde_json::json!({ "fileIds": ids }))
+            .await
+    }
+
+    async fn mods(&self, ids: Vec<u32>) -> Result<Vec<ApiMod>, ManagerError> {
+        self.post("/v1/mods", serde_json::json!({ "modIds": ids }))
+            .await
+    }
+
+    /// Downloads the files of the pack into the instance directory.
+    /// Returns the files whose authors don't allow them to be downloaded by launchers.
+    pub async fn download_files(
+        &self,
+        manifest: &CurseManifest,
+        instance_path: &Path,
+    ) -> Result<Vec<ManualDownload>, ManagerError> {
+        let files = manifest.required_files().collect::<Vec<_>>();
+        if files.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let api_files = self
+            .files(files.iter().map(|file| file.file_id).collect())
+            .await?;
+        let mods = self
+            .mods(files.iter().map(|file| file.project_id).collect())
+            .await?
+            .into_iter()
+            .map(|it| (it.id, it))
+            .collect::<HashMap<_, _>>();
+
+        let mut resolved = vec![];
+        let mut manual = vec![];
+
+        for file in files {
+            let api_file = api_files
+                .iter()
+                .find(|it| it.id == file.file_id)
+                .ok_or_else(|| {
+                    ManagerError::CurseForgeError(format!("unknown file {}", file.file_id))
+                })?;
+            let project = mods.get(&api_file.mod_id);
+
+            let folder = match project.and_then(|it| it.class_id) {
+                Some(RESOURCE_PACK_CLASS) => "resourcepacks",
+                Some(SHADER_PACK_CLASS) => "shaderpacks",
+                _ => "mods",
+            };
+
+            let relative = Path::new(folder).join(&api_file.file_name);
+            if !archive::is_enclosed(&relative) {
+                return Err(ManagerError::CurseForgeError(format!(
+                    "unexpected file name {}",
+                    api_file.file_name
+                )));
+            }
+
+            match &api_file.download_url {
+                Some(url) => resolved.push(ResolvedFile {
+                    url: url.clone(),
+                    file_name: api_file.file_name.clone(),
+                    destination: instance_path.join(&relative),
+                    sha1: api_file
+                        .hashes
+                        .iter()
+                        .find(|hash| hash.algo == SHA1_ALGORITHM)
+                        .map(|hash| hash.value.clone()),
+                }),
+                None => manual.push(ManualDownload {
+                    project_id: file.project_id,
+                    file_id: file.file_id,
+                    name: project.map_or_else(|| api_file.file_name.clone(), |it| it.name.clone()),
+                    file_name: api_file.file_name.clone(),
+                    path: format!("{}/{}", folder, api_file.file_name),
+                    url: project
+                        .and_then(|it| it.links.website_url.as_ref())
+                        .map(|url| format!("{}/files/{}", url.trim_end_matches('/'), file.file_id)),
+                }),
+            }
+        }
+
+        let results = futures::stream::iter(&resolved)
+            .map(|file| self.download_file(file))
+            .buffer_unordered(CONCURRENT_DOWNLOADS)
+            .collect::<Vec<_>>()
+            .await;
+        results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        if !manual.is_empty() {
+            warn!("{} files of {} have to be downloaded by hand", manual.len(), manifest.name);
+        }
+
+        Ok(manual)
+    }
+
+    async fn download_file(&self, file: &ResolvedFile) -> Result<(), ManagerError> {
+        let bytes = self
+            .http
+            .get(&file.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        if let Some(expected) = &file.sha1 {
+            let actual = format!("{:x}", Sha1::digest(&bytes));
+
+            if !expected.eq_ignore_ascii_case(&actual) {
+                return Err(ManagerError::ChecksumMismatch(file.file_name.clone()));
+            }
+        }
+
+        if let Some(parent) = file.destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&file.destination, &bytes)?;
+
+        Ok(())
+    }
+}
+
+/// Copies the overrides folder of the pack into the instance directory.
+pub fn apply_overrides(
+    archive: &Path,
+    manifest: &CurseManifest,
+    instance_path: &Path,
+) -> Result<(), ManagerError> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+    let folder = manifest
+        .overrides
+        .as_deref()
+        .unwrap_or(DEFAULT_OVERRIDES_FOLDER);
+
+    let extracted = archive::extract_folder(&mut zip, folder, instance_path)?;
+
+    info!("Applied {} overrides", extracted);
+    Ok(())
+}
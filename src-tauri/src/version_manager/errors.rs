@@ -12,6 +12,26 @@ pub enum ManagerError {
     IoError(#[from] io::Error),
     #[error("Asset not found")]
     NotFound,
+    #[error("Unknown version: {0}")]
+    UnknownVersion(String),
+    #[error("Unknown instance: {0}")]
+    UnknownInstance(String),
+    #[error("No instance is selected")]
+    NoElectedInstance,
+    #[error("Unknown mod: {0}")]
+    UnknownMod(String),
+    #[error("Could not install loader: {0}")]
+    LoaderInstallError(String),
+    #[error("Unknown argument placeholder: {0}")]
+    UnknownPlaceholder(String),
+    #[error("Could not read archive: {0}")]
+    ArchiveError(#[from] zip::result::ZipError),
+    #[error("Unsupported archive: {0}")]
+    UnsupportedArchive(String),
+    #[error("CurseForge error: {0}")]
+    CurseForgeError(String),
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
     #[error("Authentication error: {0}")]
     AuthenticationError(#[from] AuthError),
 }
\ No newline at end of file
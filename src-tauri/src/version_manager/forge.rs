@@ -0,0 +1,733 @@
+use crate::version_manager::archive;
+use crate::version_manager::asset::{maven_path, Artifact, Features, Library};
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::launch::CLASSPATH_SEPARATOR;
+use crate::version_manager::loaders::LoaderProfile;
+use crate::version_manager::mods::manifest_value;
+use crate::version_manager::version::VersionId;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use tauri::api::process::{Command, CommandEvent};
+use tracing::{debug, info, warn};
+use zip::ZipArchive;
+
+const INSTALL_PROFILE: &str = "install_profile.json";
+const MAVEN_FOLDER: &str = "maven";
+const JAR_MANIFEST: &str = "META-INF/MANIFEST.MF";
+
+/// Install profile of the installers since 1.12.2, which patch the client with processors.
+#[derive(Deserialize, Debug)]
+struct InstallProfile {
+    /// Path of the version profile inside the installer.
+    json: String,
+    #[serde(default)]
+    data: HashMap<String, SidedValue>,
+    #[serde(default)]
+    processors: Vec<Processor>,
+    /// Libraries the processors run with.
+    #[serde(default)]
+    libraries: Vec<Library>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SidedValue {
+    client: Option<String>,
+}
+
+/// Java program run by the installer, e.g. to deobfuscate and patch the client.
+#[derive(Deserialize, Debug)]
+struct Processor {
+    jar: String,
+    #[serde(default)]
+    classpath: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Checksums of the files it writes, by path.
+    #[serde(default)]
+    outputs: HashMap<String, String>,
+    /// Runs on every side when missing.
+    sides: Option<Vec<String>>,
+}
+
+impl Processor {
+    fn runs_on_client(&self) -> bool {
+        self.sides
+            .as_ref()
+            .map_or(true, |sides| sides.iter().any(|side| side == "client"))
+    }
+
+    /// Arguments given to java to run the processor.
+    fn command(
+        &self,
+        data: &HashMap<String, String>,
+        libraries: &Path,
+    ) -> Result<Vec<String>, ManagerError> {
+        let jar = library_path(libraries, &self.jar)?;
+        let main_class = main_class(&jar)?;
+
+        let mut classpath = vec![jar.to_string_lossy().to_string()];
+        for coordinate in self.classpath.iter() {
+            classpath.push(
+                library_path(libraries, coordinate)?
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+
+        let mut command = vec![
+            "-cp".to_string(),
+            classpath.join(CLASSPATH_SEPARATOR),
+            main_class,
+        ];
+        for argument in self.args.iter() {
+            command.push(resolve(argument, data, libraries)?);
+        }
+
+        Ok(command)
+    }
+
+    /// Files the processor writes with their checksum.
+    fn outputs(
+        &self,
+        data: &HashMap<String, String>,
+        libraries: &Path,
+    ) -> Result<Vec<(PathBuf, String)>, ManagerError> {
+        self.outputs
+            .iter()
+            .map(|(path, sha1)| {
+                Ok((
+                    PathBuf::from(resolve(path, data, libraries)?),
+                    resolve(sha1, data, libraries)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Install profile of the installers before 1.12.2, which ship the patched jar as is.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LegacyInstallProfile {
+    install: LegacyInstall,
+    version_info: LoaderProfile,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LegacyInstall {
+    /// Maven coordinate the jar is installed as.
+    path: String,
+    /// Name of the jar inside the installer.
+    file_path: String,
+}
+
+#[derive(Debug)]
+enum Installation {
+    Processed {
+        install: InstallProfile,
+        profile: LoaderProfile,
+    },
+    Legacy(LegacyInstallProfile),
+}
+
+/// Paths the processors are run with.
+pub struct ProcessorContext<'a> {
+    pub minecraft: &'a VersionId,
+    pub client_jar: &'a Path,
+    pub libraries: &'a Path,
+    /// Root of the launcher files.
+    pub root: &'a Path,
+    /// Where the files of the installer used by the processors are extracted.
+    pub work: &'a Path,
+}
+
+/// Installer of Forge or NeoForge, which both share the same format.
+#[derive(Debug)]
+pub struct ForgeInstaller {
+    path: PathBuf,
+    installation: Installation,
+}
+
+impl ForgeInstaller {
+    /// Downloads the installer to `path` from the first url serving it, unless it is already there.
+    pub async fn download(urls: &[String], path: &Path) -> Result<ForgeInstaller, ManagerError> {
+        if path.exists() {
+            match Self::open(path) {
+                Ok(installer) => return Ok(installer),
+                Err(err) => warn!("Downloading the installer again: {}", err),
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut last_error = None;
+
+        for url in urls {
+            info!("Downloading loader installer: {}", url);
+            match Self::fetch(url).await {
+                Ok(bytes) => {
+                    fs::write(path, &bytes)?;
+                    return Self::open(path);
+                }
+                Err(err) => {
+                    warn!("Installer not available at {}: {}", url, err);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            ManagerError::LoaderInstallError("No installer to download".to_string())
+        }))
+    }
+
+    async fn fetch(url: &str) -> Result<Vec<u8>, ManagerError> {
+        let bytes = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+
+        Ok(bytes.to_vec())
+    }
+
+    pub fn open(path: &Path) -> Result<ForgeInstaller, ManagerError> {
+        let mut zip = ZipArchive::new(fs::File::open(path)?)?;
+        let install = read_entry(&mut zip, INSTALL_PROFILE)?;
+
+        let installation = match serde_json::from_slice::<LegacyInstallProfile>(&install) {
+            Ok(legacy) => Installation::Legacy(legacy),
+            Err(_) => {
+                let install = serde_json::from_slice::<InstallProfile>(&install)?;
+                let profile = read_entry(&mut zip, install.json.trim_start_matches('/'))?;
+
+                Installation::Processed {
+                    profile: serde_json::from_slice(&profile)?,
+                    install,
+                }
+            }
+        };
+
+        Ok(ForgeInstaller {
+            path: path.to_path_buf(),
+            installation,
+        })
+    }
+
+    /// Profile of the installed loader, to apply on the vanilla manifest.
+    pub fn profile(&self) -> &LoaderProfile {
+        match &self.installation {
+            Installation::Processed { profile, .. } => profile,
+            Installation::Legacy(legacy) => &legacy.version_info,
+        }
+    }
+
+    /// Libraries to download before running the processors.
+    pub fn libraries(&self) -> Vec<Artifact> {
+        match &self.installation {
+            Installation::Processed { install, profile } => install
+                .libraries
+                .iter()
+                .chain(profile.libraries.iter())
+                .filter(|lib| lib.is_allowed(&Features::default()))
+                .filter_map(Library::artifact)
+                // Left empty for the files the processors generate
+                .filter(|artifact| !artifact.url.is_empty())
+                .collect(),
+            Installation::Legacy(_) => vec![],
+        }
+    }
+
+    /// Extracts the libraries shipped inside the installer to `libraries`.
+    pub fn extract_libraries(&self, libraries: &Path) -> Result<usize, ManagerError> {
+        let mut zip = ZipArchive::new(fs::File::open(&self.path)?)?;
+
+        match &self.installation {
+            Installation::Processed { .. } => {
+                archive::extract_folder(&mut zip, MAVEN_FOLDER, libraries)
+            }
+            Installation::Legacy(legacy) => {
+                let target = library_path(libraries, &legacy.install.path)?;
+                extract_entry(&mut zip, &legacy.install.file_path, &target)?;
+
+                Ok(1)
+            }
+        }
+    }
+
+    /// Runs the client processors whose outputs are missing or outdated.
+    pub async fn run_processors(&self, context: &ProcessorContext<'_>) -> Result<(), ManagerError> {
+        let install = match &self.installation {
+            Installation::Processed { install, .. } => install,
+            Installation::Legacy(_) => return Ok(()),
+        };
+
+        let data = self.data(install, context)?;
+
+        for processor in install.processors.iter().filter(|it| it.runs_on_client()) {
+            let outputs = processor.outputs(&data, context.libraries)?;
+
+            if !outputs.is_empty() && outputs_match(&outputs)? {
+                info!("Outputs of processor {} are up to date", processor.jar);
+                continue;
+            }
+
+            info!("Running processor {}", processor.jar);
+            run_java(processor.command(&data, context.libraries)?).await?;
+
+            for (path, sha1) in outputs.iter() {
+                if !output_matches(path, sha1)? {
+                    return Err(ManagerError::ChecksumMismatch(path.display().to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Values the processor arguments refer to as `{KEY}`.
+    fn data(
+        &self,
+        install: &InstallProfile,
+        context: &ProcessorContext,
+    ) -> Result<HashMap<String, String>, ManagerError> {
+        let mut zip = ZipArchive::new(fs::File::open(&self.path)?)?;
+        let mut data = HashMap::new();
+
+        for (key, value) in install.data.iter() {
+            let value = match &value.client {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let resolved = match value.strip_prefix('/') {
+                // Files of the installer, e.g. the binary patches
+                Some(entry) => {
+                    let target = context.work.join(entry);
+                    extract_entry(&mut zip, entry, &target)?;
+                    target.to_string_lossy().to_string()
+                }
+                None => resolve(value, &HashMap::new(), context.libraries)?,
+            };
+
+            data.insert(key.clone(), resolved);
+        }
+
+        let builtins = [
+            ("SIDE", "client".to_string()),
+            (
+                "MINECRAFT_JAR",
+                context.client_jar.to_string_lossy().to_string(),
+            ),
+            ("MINECRAFT_VERSION", context.minecraft.to_string()),
+            ("ROOT", context.root.to_string_lossy().to_string()),
+            ("INSTALLER", self.path.to_string_lossy().to_string()),
+            (
+                "LIBRARY_DIR",
+                context.libraries.to_string_lossy().to_string(),
+            ),
+        ];
+
+        for (key, value) in builtins {
+            data.insert(key.to_string(), value);
+        }
+
+        Ok(data)
+    }
+}
+
+/// Resolves an argument of a processor: `[coordinate]` is the path of a library,
+/// `'literal'` is kept as is and `{KEY}` tokens are replaced by their data value.
+fn resolve(
+    argument: &str,
+    data: &HashMap<String, String>,
+    libraries: &Path,
+) -> Result<String, ManagerError> {
+    if let Some(coordinate) = argument
+        .strip_prefix('[')
+        .and_then(|it| it.strip_suffix(']'))
+    {
+        return Ok(library_path(libraries, coordinate)?
+            .to_string_lossy()
+            .to_string());
+    }
+
+    if let Some(literal) = argument
+        .strip_prefix('\'')
+        .and_then(|it| it.strip_suffix('\''))
+    {
+        return Ok(literal.to_string());
+    }
+
+    let mut result = String::with_capacity(argument.len());
+    let mut rest = argument;
+
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let key = &rest[start + 1..end];
+        let value = data.get(key).ok_or_else(|| {
+            ManagerError::LoaderInstallError(format!("Unknown processor data: {}", key))
+        })?;
+
+        result.push_str(&rest[..start]);
+        result.push_str(value);
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn library_path(libraries: &Path, coordinate: &str) -> Result<PathBuf, ManagerError> {
+    maven_path(coordinate)
+        .map(|path| libraries.join(path))
+        .ok_or_else(|| ManagerError::LoaderInstallError(format!("Invalid library: {}", coordinate)))
+}
+
+fn main_class(jar: &Path) -> Result<String, ManagerError> {
+    let mut zip = ZipArchive::new(fs::File::open(jar)?)?;
+    let manifest = read_entry(&mut zip, JAR_MANIFEST)?;
+
+    manifest_value(&manifest, "Main-Class").ok_or_else(|| {
+        ManagerError::LoaderInstallError(format!("{} has no main class", jar.display()))
+    })
+}
+
+fn read_entry(zip: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>, ManagerError> {
+    let mut entry = zip.by_name(name)?;
+    let mut bytes = vec![];
+    entry.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn extract_entry(
+    zip: &mut ZipArchive<fs::File>,
+    name: &str,
+    to: &Path,
+) -> Result<(), ManagerError> {
+    if !archive::is_enclosed(Path::new(name)) {
+        return Err(ManagerError::UnsupportedArchive(format!(
+            "Invalid entry: {}",
+            name
+        )));
+    }
+
+    let mut entry = zip.by_name(name)?;
+
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    io::copy(&mut entry, &mut fs::File::create(to)?)?;
+
+    Ok(())
+}
+
+fn output_matches(path: &Path, sha1: &str) -> Result<bool, ManagerError> {
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let mut hasher = Sha1::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()) == sha1)
+}
+
+fn outputs_match(outputs: &[(PathBuf, String)]) -> Result<bool, ManagerError> {
+    for (path, sha1) in outputs {
+        if !output_matches(path, sha1)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+async fn run_java(arguments: Vec<String>) -> Result<(), ManagerError> {
+    let (mut rx, _) = Command::new("java")
+        .args(arguments)
+        .spawn()
+        .map_err(|err| {
+            ManagerError::LoaderInstallError(format!("Could not start java: {}", err))
+        })?;
+
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stdout(line) => debug!("{}", line),
+            CommandEvent::Stderr(line) => warn!("{}", line),
+            CommandEvent::Terminated(end) if end.code == Some(0) => return Ok(()),
+            CommandEvent::Terminated(end) => {
+                return Err(ManagerError::LoaderInstallError(format!(
+                    "Processor exited with code {:?}",
+                    end.code
+                )))
+            }
+            event => debug!("Processor event: {:?}", event),
+        }
+    }
+
+    Err(ManagerError::LoaderInstallError(
+        "Processor stopped unexpectedly".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestServer;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const INSTALL: &str = r#"{
+        "spec": 1,
+        "json": "/version.json",
+        "data": {
+            "MAPPINGS": {"client": "[de.oceanlabs.mcp:mcp_config:1.20.1:mappings@txt]", "server": "[x:y:1]"},
+            "PATCHED_SHA": {"client": "'0123'", "server": "'4567'"},
+            "BINPATCH": {"client": "/data/client.lzma", "server": "/data/server.lzma"}
+        },
+        "processors": [
+            {
+                "sides": ["server"],
+                "jar": "net.minecraftforge:installertools:1.3.0",
+                "args": ["--task", "EXTRACT_SERVER"]
+            },
+            {
+                "jar": "net.minecraftforge:installertools:1.3.0",
+                "classpath": ["net.sf.jopt-simple:jopt-simple:5.0.4"],
+                "args": ["--input", "{MINECRAFT_JAR}", "--mappings", "{MAPPINGS}", "--patch", "{BINPATCH}",
+                         "--output", "[net.minecraftforge:forge:1.20.1-47.2.0:client]", "--side", "{SIDE}"],
+                "outputs": {"[net.minecraftforge:forge:1.20.1-47.2.0:client]": "{PATCHED_SHA}"}
+            }
+        ],
+        "libraries": [
+            {"name": "net.minecraftforge:installertools:1.3.0", "downloads": {"artifact": {
+                "path": "net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar",
+                "sha1": "aa", "size": 1,
+                "url": "https://maven.minecraftforge.net/net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar"
+            }}}
+        ]
+    }"#;
+
+    const VERSION: &str = r#"{
+        "id": "1.20.1-forge-47.2.0",
+        "inheritsFrom": "1.20.1",
+        "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+        "arguments": {"game": ["--launchTarget", "forgeclient"]},
+        "libraries": [
+            {"name": "net.minecraftforge:forge:1.20.1-47.2.0:client", "downloads": {"artifact": {
+                "path": "net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-client.jar",
+                "sha1": "0123", "size": 0, "url": ""
+            }}}
+        ]
+    }"#;
+
+    const LEGACY_INSTALL: &str = r#"{
+        "install": {
+            "path": "net.minecraftforge:forge:1.7.10-10.13.4.1614-1.7.10",
+            "filePath": "forge-1.7.10-10.13.4.1614-1.7.10-universal.jar"
+        },
+        "versionInfo": {
+            "id": "1.7.10-Forge10.13.4.1614-1.7.10",
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "minecraftArguments": "--tweakClass cpw.mods.fml.common.launcher.FMLTweaker",
+            "libraries": [{"name": "net.minecraft:launchwrapper:1.12"}]
+        }
+    }"#;
+
+    fn zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+
+        for (name, contents) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+
+        zip.finish().unwrap();
+    }
+
+    fn installer(path: &Path) {
+        zip(path, &[
+            ("install_profile.json", INSTALL.as_bytes()),
+            ("version.json", VERSION.as_bytes()),
+            ("data/client.lzma", b"patches"),
+            ("maven/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar", b"forge"),
+        ]);
+    }
+
+    #[test]
+    fn reads_processed_installers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installer.jar");
+        let libraries = dir.path().join("libs");
+        installer(&path);
+
+        let installer = ForgeInstaller::open(&path).unwrap();
+        let extracted = installer.extract_libraries(&libraries).unwrap();
+
+        assert_eq!(installer.profile().id, "1.20.1-forge-47.2.0");
+        assert_eq!(extracted, 1);
+        assert!(libraries
+            .join("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar")
+            .exists());
+        // The client jar is left to the processors
+        let paths = installer
+            .libraries()
+            .into_iter()
+            .filter_map(|it| it.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar"]
+        );
+    }
+
+    #[test]
+    fn reads_legacy_installers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installer.jar");
+        let libraries = dir.path().join("libs");
+        zip(
+            &path,
+            &[
+                ("install_profile.json", LEGACY_INSTALL.as_bytes()),
+                ("forge-1.7.10-10.13.4.1614-1.7.10-universal.jar", b"forge"),
+            ],
+        );
+
+        let installer = ForgeInstaller::open(&path).unwrap();
+        installer.extract_libraries(&libraries).unwrap();
+
+        assert_eq!(
+            installer.profile().main_class,
+            "net.minecraft.launchwrapper.Launch"
+        );
+        assert!(installer.libraries().is_empty());
+        let jar = "net/minecraftforge/forge/1.7.10-10.13.4.1614-1.7.10/\
+                   forge-1.7.10-10.13.4.1614-1.7.10.jar";
+        assert_eq!(fs::read(libraries.join(jar)).unwrap(), b"forge");
+    }
+
+    #[test]
+    fn resolves_the_processor_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installer.jar");
+        let libraries = dir.path().join("libs");
+        let work = dir.path().join("work");
+        let client_jar = dir.path().join("1.20.1.jar");
+        installer(&path);
+
+        let tools =
+            libraries.join("net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar");
+        fs::create_dir_all(tools.parent().unwrap()).unwrap();
+        let manifest: &[u8] =
+            b"Manifest-Version: 1.0\r\nMain-Class: net.minecraftforge.installertools.ConsoleTool\r\n";
+        zip(&tools, &[(JAR_MANIFEST, manifest)]);
+
+        let installer = ForgeInstaller::open(&path).unwrap();
+        let install = match &installer.installation {
+            Installation::Processed { install, .. } => install,
+            Installation::Legacy(_) => unreachable!(),
+        };
+        let context = ProcessorContext {
+            minecraft: &VersionId::new("1.20.1"),
+            client_jar: &client_jar,
+            libraries: &libraries,
+            root: dir.path(),
+            work: &work,
+        };
+
+        let data = installer.data(install, &context).unwrap();
+        let processors = install
+            .processors
+            .iter()
+            .filter(|it| it.runs_on_client())
+            .collect::<Vec<_>>();
+        let command = processors[0].command(&data, &libraries).unwrap();
+        let outputs = processors[0].outputs(&data, &libraries).unwrap();
+
+        let library = |path: &str| libraries.join(path).to_string_lossy().to_string();
+        let client =
+            library("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-client.jar");
+        assert_eq!(processors.len(), 1);
+        assert_eq!(command[0], "-cp");
+        assert_eq!(
+            command[1],
+            format!(
+                "{}{}{}",
+                tools.to_string_lossy(),
+                CLASSPATH_SEPARATOR,
+                library("net/sf/jopt-simple/jopt-simple/5.0.4/jopt-simple-5.0.4.jar")
+            )
+        );
+        assert_eq!(
+            command[2..],
+            [
+                "net.minecraftforge.installertools.ConsoleTool".to_string(),
+                "--input".to_string(),
+                client_jar.to_string_lossy().to_string(),
+                "--mappings".to_string(),
+                library("de/oceanlabs/mcp/mcp_config/1.20.1/mcp_config-1.20.1-mappings.txt"),
+                "--patch".to_string(),
+                work.join("data/client.lzma").to_string_lossy().to_string(),
+                "--output".to_string(),
+                client.clone(),
+                "--side".to_string(),
+                "client".to_string(),
+            ]
+        );
+        assert_eq!(fs::read(work.join("data/client.lzma")).unwrap(), b"patches");
+        assert_eq!(outputs, vec![(PathBuf::from(client), "0123".to_string())]);
+        assert!(!outputs_match(&outputs).unwrap());
+    }
+
+    #[test]
+    fn fails_on_unknown_processor_data() {
+        let result = resolve("{MISSING}", &HashMap::new(), Path::new("libs"));
+
+        assert!(matches!(result, Err(ManagerError::LoaderInstallError(_))));
+        assert_eq!(
+            resolve("'{MISSING}'", &HashMap::new(), Path::new("libs")).unwrap(),
+            "{MISSING}"
+        );
+    }
+
+    #[tokio::test]
+    async fn downloads_the_installer_from_the_first_url_serving_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = dir.path().join("fixture.jar");
+        let path = dir
+            .path()
+            .join("loaders")
+            .join("forge-47.2.0-installer.jar");
+        installer(&fixture);
+        let bytes = fs::read(&fixture).unwrap();
+        let server = TestServer::start(move |request| match request.path.as_str() {
+            "/second.jar" => (200, bytes.clone()),
+            _ => (404, vec![]),
+        })
+        .await;
+        let urls = vec![server.url("/first.jar"), server.url("/second.jar")];
+
+        let installer = ForgeInstaller::download(&urls, &path).await.unwrap();
+        ForgeInstaller::download(&urls, &path).await.unwrap();
+
+        assert_eq!(installer.profile().id, "1.20.1-forge-47.2.0");
+        assert_eq!(server.requests().len(), 2);
+        assert!(
+            ForgeInstaller::download(&urls[..1], &dir.path().join("missing.jar"))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use crate::VersionId;
 use serde::{Deserialize, Serialize};
 
-pub const AVAILABLE_GAMES: &[(&str, &str, VersionId); 1] =
-    &[("thebox_1.0", "The Box", VersionId::V1_19_3)];
+/// Id, name, version and default server of the games.
+pub const AVAILABLE_GAMES: &[(&str, &str, &str, Option<&str>); 1] =
+    &[("thebox_1.0", "The Box", "1.19.3", None)];
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct GameStorage {
@@ -14,14 +15,17 @@ pub struct Game {
     pub id: String,
     pub name: String,
     pub version: VersionId,
+    /// Server joined when the game is started without a Quick Play target.
+    pub server: Option<String>,
 }
 
 impl Game {
-    pub fn from_static((id, name, version): &(&str, &str, VersionId)) -> Game {
+    pub fn from_static((id, name, version, server): &(&str, &str, &str, Option<&str>)) -> Game {
         Game {
             id: id.to_string(),
             name: name.to_string(),
-            version: *version,
+            version: VersionId::new(version),
+            server: server.map(String::from),
         }
     }
 }
@@ -0,0 +1,208 @@
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::games::{Game, AVAILABLE_GAMES};
+use crate::version_manager::version::VersionId;
+use serde::{Deserialize, Serialize};
+
+/// Mod loader an instance runs with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Loader {
+    Vanilla,
+    Fabric { version: String },
+    Quilt { version: String },
+    Forge { version: String },
+    NeoForge { version: String },
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Loader::Vanilla
+    }
+}
+
+/// Where the contents of an instance come from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PackSource {
+    /// Created by the user.
+    Custom,
+    /// One of the games we distribute, see [`AVAILABLE_GAMES`].
+    Game { id: String },
+    /// Modrinth modpack, by the name and version of its index.
+    Modrinth { name: String, version: String },
+    /// CurseForge modpack, by the name and version of its manifest.
+    CurseForge { name: String, version: Option<String> },
+}
+
+impl Default for PackSource {
+    fn default() -> Self {
+        PackSource::Custom
+    }
+}
+
+/// Game directory with its own version, loader and settings, stored in `instances/<id>`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Instance {
+    pub id: String,
+    pub name: String,
+    /// Url or data uri of the icon shown by the frontend.
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub version: VersionId,
+    #[serde(default)]
+    pub loader: Loader,
+    #[serde(default)]
+    pub source: PackSource,
+    /// Seconds since the epoch.
+    #[serde(default)]
+    pub created: u64,
+}
+
+impl Instance {
+    pub fn new(id: String, name: String, version: VersionId, loader: Loader, source: PackSource) -> Instance {
+        Instance {
+            id,
+            name,
+            icon: None,
+            version,
+            loader,
+            source,
+            created: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs(),
+        }
+    }
+
+    /// Game the instance was created from, if any.
+    pub fn game(&self) -> Option<Game> {
+        match &self.source {
+            PackSource::Game { id } => AVAILABLE_GAMES
+                .iter()
+                .find(|game| game.0 == id.as_str())
+                .map(Game::from_static),
+            _ => None,
+        }
+    }
+}
+
+/// Instances known by the launcher, stored in `instances.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct InstanceStorage {
+    pub instances: Vec<Instance>,
+    #[serde(default)]
+    pub elected_instance: Option<String>,
+}
+
+impl InstanceStorage {
+    pub fn find(&self, id: &str) -> Result<&Instance, ManagerError> {
+        self.instances
+            .iter()
+            .find(|instance| instance.id == id)
+            .ok_or_else(|| ManagerError::UnknownInstance(id.to_string()))
+    }
+
+    pub fn find_mut(&mut self, id: &str) -> Result<&mut Instance, ManagerError> {
+        self.instances
+            .iter_mut()
+            .find(|instance| instance.id == id)
+            .ok_or_else(|| ManagerError::UnknownInstance(id.to_string()))
+    }
+
+    pub fn insert(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<Instance, ManagerError> {
+        let index = self
+            .instances
+            .iter()
+            .position(|instance| instance.id == id)
+            .ok_or_else(|| ManagerError::UnknownInstance(id.to_string()))?;
+
+        if self.elected_instance.as_deref() == Some(id) {
+            self.elected_instance = None;
+        }
+
+        Ok(self.instances.remove(index))
+    }
+
+    pub fn elect(&mut self, id: &str) -> Result<(), ManagerError> {
+        self.find(id)?;
+        self.elected_instance = Some(id.to_string());
+        Ok(())
+    }
+
+    /// Id for a new instance, derived from its name. The id is also the name
+    /// of the instance directory, so only a safe subset of characters is kept.
+    /// `exists` tells whether a directory is already left at the path of an id.
+    pub fn unique_id(&self, name: &str, exists: impl Fn(&str) -> bool) -> String {
+        let mut base = name
+            .trim()
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+            .collect::<String>()
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        if base.is_empty() || base.chars().all(|c| c == '.') {
+            base = "instance".to_string();
+        }
+
+        let mut id = base.clone();
+        let mut suffix = 2;
+
+        while self.instances.iter().any(|instance| instance.id == id) || exists(&id) {
+            id = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+
+        id
+    }
+
+    /// Games used to map to an instance directory named after them,
+    /// so each one gets an instance with the same id.
+    /// Returns whether any instance was added.
+    pub fn ensure_games(&mut self) -> bool {
+        let mut changed = false;
+
+        for game in AVAILABLE_GAMES.iter().map(Game::from_static) {
+            let exists = self.instances.iter().any(|instance| {
+                instance.id == game.id
+                    || instance.source == PackSource::Game { id: game.id.clone() }
+            });
+
+            if !exists {
+                let source = PackSource::Game { id: game.id.clone() };
+                self.insert(Instance::new(game.id, game.name, game.version, Loader::Vanilla, source));
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_ids_taken_by_instances_or_directories() {
+        let mut storage = InstanceStorage::default();
+        storage.insert(Instance::new(
+            "my-world".to_string(),
+            "My World".to_string(),
+            VersionId::new("1.20.1"),
+            Loader::Vanilla,
+            PackSource::Custom,
+        ));
+
+        assert_eq!(storage.unique_id("My World", |_| false), "my-world-2");
+        assert_eq!(storage.unique_id("My World", |id| id == "my-world-2"), "my-world-3");
+        assert_eq!(storage.unique_id("Other", |id| id == "other"), "other-2");
+    }
+}
@@ -0,0 +1,230 @@
+use crate::files::instance::InstanceSettings;
+use crate::version_manager::asset::Features;
+use crate::version_manager::errors::ManagerError;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+const REDACTED: &str = "<redacted>";
+const DEFAULT_SERVER_PORT: u16 = 25565;
+
+pub const CLASSPATH_SEPARATOR: &str = if cfg!(target_os = "windows") { ";" } else { ":" };
+
+/// Where the game drops the player once started, instead of the title screen.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuickPlay {
+    /// Name of the world folder inside `saves`.
+    Singleplayer { world: String },
+    /// Address of the server, with an optional port.
+    Multiplayer { address: String },
+    /// Id of the realm.
+    Realms { realm: String },
+}
+
+impl QuickPlay {
+    /// Splits a `host[:port]` address, as expected by the legacy `--server` and `--port`.
+    pub fn split_address(address: &str) -> (String, u16) {
+        match address.rsplit_once(':') {
+            Some((host, port)) => match port.parse() {
+                Ok(port) => (host.to_string(), port),
+                Err(_) => (address.to_string(), DEFAULT_SERVER_PORT),
+            },
+            None => (address.to_string(), DEFAULT_SERVER_PORT),
+        }
+    }
+}
+
+/// Choices made by the caller when launching a game.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    pub features: Features,
+    /// Fails the launch when an argument uses a placeholder we don't know,
+    /// instead of passing it through untouched.
+    pub strict_arguments: bool,
+    /// Settings of the instance, already merged with the launcher defaults.
+    pub settings: InstanceSettings,
+    pub quick_play: Option<QuickPlay>,
+}
+
+impl LaunchOptions {
+    pub fn new(
+        settings: InstanceSettings,
+        quick_play: Option<QuickPlay>,
+        strict_arguments: bool,
+    ) -> LaunchOptions {
+        LaunchOptions {
+            features: Features {
+                has_custom_resolution: settings.resolution.is_some(),
+                is_quick_play_singleplayer: matches!(quick_play, Some(QuickPlay::Singleplayer { .. })),
+                is_quick_play_multiplayer: matches!(quick_play, Some(QuickPlay::Multiplayer { .. })),
+                is_quick_play_realms: matches!(quick_play, Some(QuickPlay::Realms { .. })),
+                ..Default::default()
+            },
+            strict_arguments,
+            settings,
+            quick_play,
+        }
+    }
+}
+
+/// Values of the `${...}` placeholders found in the manifest arguments.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchContext {
+    pub auth_player_name: String,
+    pub auth_uuid: String,
+    pub auth_access_token: String,
+    pub auth_xuid: String,
+    pub clientid: String,
+    pub user_type: String,
+    pub user_properties: String,
+    pub version_name: String,
+    pub version_type: String,
+    pub game_directory: String,
+    pub assets_root: String,
+    pub game_assets: String,
+    pub assets_index_name: String,
+    pub natives_directory: String,
+    pub classpath: String,
+    /// Used by the module path of modern Forge versions.
+    pub library_directory: String,
+    pub classpath_separator: String,
+    pub launcher_name: String,
+    pub launcher_version: String,
+    pub resolution_width: String,
+    pub resolution_height: String,
+    pub quick_play_singleplayer: String,
+    pub quick_play_multiplayer: String,
+    pub quick_play_realms: String,
+}
+
+impl LaunchContext {
+    fn value(&self, key: &str) -> Option<&str> {
+        let value = match key {
+            "auth_player_name" => &self.auth_player_name,
+            "auth_uuid" => &self.auth_uuid,
+            "auth_access_token" => &self.auth_access_token,
+            // Used instead of the access token by some legacy versions.
+            "auth_session" => &self.auth_access_token,
+            "auth_xuid" => &self.auth_xuid,
+            "clientid" => &self.clientid,
+            "user_type" => &self.user_type,
+            "user_properties" => &self.user_properties,
+            "version_name" => &self.version_name,
+            "version_type" => &self.version_type,
+            "game_directory" => &self.game_directory,
+            "assets_root" => &self.assets_root,
+            "game_assets" => &self.game_assets,
+            "assets_index_name" => &self.assets_index_name,
+            "natives_directory" => &self.natives_directory,
+            "classpath" => &self.classpath,
+            "library_directory" => &self.library_directory,
+            "classpath_separator" => &self.classpath_separator,
+            "launcher_name" => &self.launcher_name,
+            "launcher_version" => &self.launcher_version,
+            "resolution_width" => &self.resolution_width,
+            "resolution_height" => &self.resolution_height,
+            "quickPlaySingleplayer" => &self.quick_play_singleplayer,
+            "quickPlayMultiplayer" => &self.quick_play_multiplayer,
+            "quickPlayRealms" => &self.quick_play_realms,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Replaces every placeholder of the argument.
+    /// Unknown placeholders are kept as they are, unless `strict` is set.
+    pub fn substitute(&self, argument: &str, strict: bool) -> Result<String, ManagerError> {
+        let mut result = String::with_capacity(argument.len());
+        let mut rest = argument;
+
+        while let Some(start) = rest.find("${") {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                // Not a placeholder, just a stray `${`
+                None => break,
+            };
+
+            let key = &rest[start + 2..end];
+            result.push_str(&rest[..start]);
+
+            match self.value(key) {
+                Some(value) => result.push_str(value),
+                None if strict => return Err(ManagerError::UnknownPlaceholder(key.to_string())),
+                None => {
+                    warn!("Unknown argument placeholder: {}", key);
+                    result.push_str(&rest[start..=end]);
+                }
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        result.push_str(rest);
+
+        Ok(result)
+    }
+
+    /// Copy of the context which is safe to log.
+    pub fn redacted(&self) -> LaunchContext {
+        LaunchContext {
+            auth_access_token: REDACTED.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> LaunchContext {
+        LaunchContext {
+            auth_player_name: "Notch".to_string(),
+            auth_uuid: "069a79f444e94726a5befca90e38aaf5".to_string(),
+            auth_access_token: "secret-token".to_string(),
+            game_directory: "/games/vanilla".to_string(),
+            resolution_width: "854".to_string(),
+            resolution_height: "480".to_string(),
+            ..LaunchContext::default()
+        }
+    }
+
+    #[test]
+    fn substitutes_every_placeholder_of_an_argument() {
+        let argument = context()
+            .substitute("${resolution_width}x${resolution_height} in ${game_directory}/", false)
+            .unwrap();
+
+        assert_eq!(argument, "854x480 in /games/vanilla/");
+    }
+
+    #[test]
+    fn keeps_unknown_placeholders_unless_strict() {
+        let context = context();
+
+        assert_eq!(
+            context.substitute("--user ${auth_player_name} ${unknown}", false).unwrap(),
+            "--user Notch ${unknown}"
+        );
+        assert!(matches!(
+            context.substitute("--user ${auth_player_name} ${unknown}", true),
+            Err(ManagerError::UnknownPlaceholder(key)) if key == "unknown"
+        ));
+        assert_eq!(context.substitute("${stray", true).unwrap(), "${stray");
+    }
+
+    #[test]
+    fn redacts_the_access_token_and_the_legacy_session() {
+        let context = context();
+        let redacted = context.redacted();
+
+        assert_eq!(context.substitute("${auth_session}", true).unwrap(), "secret-token");
+        for argument in ["${auth_access_token}", "${auth_session}"] {
+            let value = redacted.substitute(argument, true).unwrap();
+
+            assert_eq!(value, REDACTED);
+        }
+        assert_eq!(redacted.substitute("${auth_player_name}", true).unwrap(), "Notch");
+    }
+}
@@ -0,0 +1,386 @@
+use crate::version_manager::asset::{Arguments, Library, VersionManifest};
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::instances::Loader;
+use crate::version_manager::version::VersionId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+const FABRIC_META: &str = "https://meta.fabricmc.net/v2";
+const QUILT_META: &str = "https://meta.quiltmc.org/v3";
+const FORGE_MAVEN: &str = "https://maven.minecraftforge.net";
+const NEOFORGE_MAVEN: &str = "https://maven.neoforged.net/releases";
+
+/// Where the loaders are downloaded from.
+#[derive(Clone, Debug)]
+pub struct LoaderSources {
+    pub fabric_meta: String,
+    pub quilt_meta: String,
+    pub forge_maven: String,
+    pub neoforge_maven: String,
+}
+
+impl Default for LoaderSources {
+    fn default() -> Self {
+        LoaderSources {
+            fabric_meta: FABRIC_META.to_string(),
+            quilt_meta: QUILT_META.to_string(),
+            forge_maven: FORGE_MAVEN.to_string(),
+            neoforge_maven: NEOFORGE_MAVEN.to_string(),
+        }
+    }
+}
+
+impl LoaderSources {
+    /// Url of the version profile served by the Fabric and Quilt metadata servers.
+    pub fn profile_url(&self, loader: &Loader, minecraft: &VersionId) -> Option<String> {
+        let (meta, version) = match loader {
+            Loader::Fabric { version } => (&self.fabric_meta, version),
+            Loader::Quilt { version } => (&self.quilt_meta, version),
+            _ => return None,
+        };
+
+        Some(format!(
+            "{}/versions/loader/{}/{}/profile/json",
+            meta, minecraft, version
+        ))
+    }
+
+    /// Urls the installer may be found at, the first one being the most likely.
+    pub fn installer_urls(&self, loader: &Loader, minecraft: &VersionId) -> Vec<String> {
+        match loader {
+            Loader::Forge { version } => {
+                // Versions of the same Minecraft release were published under a few names
+                let full = match version.starts_with(&format!("{}-", minecraft)) {
+                    true => vec![version.clone()],
+                    false => vec![
+                        format!("{}-{}", minecraft, version),
+                        format!("{}-{}-{}", minecraft, version, minecraft),
+                    ],
+                };
+
+                full.iter()
+                    .map(|full| {
+                        format!(
+                            "{}/net/minecraftforge/forge/{}/forge-{}-installer.jar",
+                            self.forge_maven, full, full
+                        )
+                    })
+                    .collect()
+            }
+            // NeoForge for 1.20.1 was still published as a fork of Forge
+            Loader::NeoForge { version } if minecraft.as_str() == "1.20.1" => vec![format!(
+                "{}/net/neoforged/forge/1.20.1-{}/forge-1.20.1-{}-installer.jar",
+                self.neoforge_maven, version, version
+            )],
+            Loader::NeoForge { version } => vec![format!(
+                "{}/net/neoforged/neoforge/{}/neoforge-{}-installer.jar",
+                self.neoforge_maven, version, version
+            )],
+            _ => vec![],
+        }
+    }
+}
+
+/// Name of the loader and its version, as used for the files of the loader.
+pub fn loader_name(loader: &Loader) -> Option<String> {
+    match loader {
+        Loader::Vanilla => None,
+        Loader::Fabric { version } => Some(format!("fabric-{}", version)),
+        Loader::Quilt { version } => Some(format!("quilt-{}", version)),
+        Loader::Forge { version } => Some(format!("forge-{}", version)),
+        Loader::NeoForge { version } => Some(format!("neoforge-{}", version)),
+    }
+}
+
+/// Version profile of a loader, which extends the manifest of the version it inherits from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoaderProfile {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherits_from: Option<String>,
+    pub main_class: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Arguments>,
+    /// Replaces the game arguments of legacy versions, e.g. to add the Forge tweaker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minecraft_arguments: Option<String>,
+    #[serde(default)]
+    pub libraries: Vec<Library>,
+}
+
+impl LoaderProfile {
+    pub fn read(path: &Path) -> Result<Option<LoaderProfile>, ManagerError> {
+        match path.exists() {
+            true => Ok(Some(serde_json::from_slice(&fs::read(path)?)?)),
+            false => Ok(None),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), ManagerError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_vec(self)?)?;
+
+        Ok(())
+    }
+
+    /// Fails when the profile was made for another version of the game.
+    pub fn ensure_inherits(&self, minecraft: &VersionId) -> Result<(), ManagerError> {
+        match &self.inherits_from {
+            Some(parent) if parent != minecraft.as_str() => Err(ManagerError::LoaderInstallError(
+                format!("{} is made for {}, not {}", self.id, parent, minecraft),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Merges the profile into the manifest the way the vanilla launcher does.
+    pub fn apply(self, manifest: &mut VersionManifest) {
+        manifest.main_class = self.main_class;
+
+        // Legacy profiles repeat the vanilla libraries, whose downloads only vanilla lists
+        let vanilla = manifest
+            .libraries
+            .iter()
+            .map(|lib| lib.name.clone())
+            .collect::<HashSet<_>>();
+        let libraries = self
+            .libraries
+            .into_iter()
+            .filter(|lib| !vanilla.contains(&lib.name))
+            .collect::<Vec<_>>();
+
+        // Loaders ship their own version of some vanilla libraries, e.g. ASM
+        let replaced = libraries.iter().map(Library::key).collect::<HashSet<_>>();
+        manifest
+            .libraries
+            .retain(|lib| !replaced.contains(&lib.key()));
+        manifest.libraries.splice(0..0, libraries);
+
+        if let Some(arguments) = self.arguments {
+            let game = manifest.game_arguments();
+            let jvm = manifest.jvm_arguments();
+            let merged = manifest.arguments.get_or_insert(Arguments { game, jvm });
+
+            merged.game.extend(arguments.game);
+            merged.jvm.extend(arguments.jvm);
+        }
+
+        if let Some(arguments) = self.minecraft_arguments {
+            manifest.minecraft_arguments = Some(arguments);
+        }
+    }
+}
+
+/// Fetches the profile of a Fabric or Quilt loader, unless it is already cached at `path`.
+pub async fn fetch_profile(url: &str, path: &Path) -> Result<LoaderProfile, ManagerError> {
+    if let Some(profile) = LoaderProfile::read(path)? {
+        return Ok(profile);
+    }
+
+    info!("Downloading loader profile: {}", url);
+    let profile = reqwest::get(url)
+        .await?
+        .error_for_status()?
+        .json::<LoaderProfile>()
+        .await?;
+
+    profile.write(path)?;
+
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{json, TestServer};
+    use serde_json::json;
+
+    const FABRIC_PROFILE: &str = r#"{
+        "id": "fabric-loader-0.14.21-1.20.1",
+        "inheritsFrom": "1.20.1",
+        "type": "release",
+        "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+        "arguments": {
+            "game": [],
+            "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "]
+        },
+        "libraries": [
+            {"name": "org.ow2.asm:asm:9.5", "url": "https://maven.fabricmc.net/"},
+            {"name": "net.fabricmc:fabric-loader:0.14.21", "url": "https://maven.fabricmc.net/"}
+        ]
+    }"#;
+
+    /// Shaped like the `versionInfo` of the 1.12.2 Forge installer.
+    const LEGACY_FORGE_PROFILE: &str = r#"{
+        "id": "1.12.2-forge1.12.2-14.23.5.2847",
+        "mainClass": "net.minecraft.launchwrapper.Launch",
+        "minecraftArguments": "--username ${auth_player_name} --tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker",
+        "libraries": [
+            {"name": "net.minecraftforge:forge:1.12.2-14.23.5.2847", "url": "https://maven.minecraftforge.net/"},
+            {"name": "com.mojang:patchy:1.1"}
+        ]
+    }"#;
+
+    fn manifest(arguments: &str) -> VersionManifest {
+        let artifact = json!({"sha1": "", "size": 0, "url": "https://example.com/client.jar"});
+        let mut manifest = json!({
+            "id": "1.20.1",
+            "libraries": [
+                {"name": "org.ow2.asm:asm:9.3", "downloads": {}},
+                {"name": "com.mojang:patchy:1.1", "downloads": {"artifact": artifact}},
+                {"name": "com.mojang:brigadier:1.1.8", "downloads": {}}
+            ],
+            "downloads": {"client": artifact, "server": artifact},
+            "assetIndex": artifact,
+            "assets": "5",
+            "mainClass": "net.minecraft.client.main.Main"
+        });
+        let arguments: serde_json::Value = serde_json::from_str(arguments).unwrap();
+        manifest
+            .as_object_mut()
+            .unwrap()
+            .extend(arguments.as_object().unwrap().clone());
+
+        serde_json::from_value(manifest).unwrap()
+    }
+
+    fn names(manifest: &VersionManifest) -> Vec<&str> {
+        manifest
+            .libraries
+            .iter()
+            .map(|lib| lib.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn puts_the_loader_libraries_first() {
+        let mut manifest = manifest(r#"{"arguments": {"game": ["--demo"], "jvm": ["-cp"]}}"#);
+        let profile: LoaderProfile = serde_json::from_str(FABRIC_PROFILE).unwrap();
+
+        profile.apply(&mut manifest);
+
+        assert_eq!(
+            manifest.main_class,
+            "net.fabricmc.loader.impl.launch.knot.KnotClient"
+        );
+        assert_eq!(
+            names(&manifest),
+            vec![
+                "org.ow2.asm:asm:9.5",
+                "net.fabricmc:fabric-loader:0.14.21",
+                "com.mojang:patchy:1.1",
+                "com.mojang:brigadier:1.1.8",
+            ]
+        );
+        assert_eq!(manifest.jvm_arguments().len(), 2);
+        assert_eq!(manifest.game_arguments().len(), 1);
+    }
+
+    #[test]
+    fn replaces_the_legacy_arguments() {
+        let mut manifest = manifest(r#"{"minecraftArguments": "--username ${auth_player_name}"}"#);
+        let profile: LoaderProfile = serde_json::from_str(LEGACY_FORGE_PROFILE).unwrap();
+
+        profile.apply(&mut manifest);
+
+        assert_eq!(manifest.main_class, "net.minecraft.launchwrapper.Launch");
+        assert!(manifest.arguments.is_none());
+        assert_eq!(manifest.game_arguments().len(), 4);
+        // The vanilla entry is kept since it is the only one telling where to download it
+        let patchy = manifest
+            .libraries
+            .iter()
+            .find(|lib| lib.name == "com.mojang:patchy:1.1");
+        assert!(patchy.unwrap().downloads.artifact.is_some());
+        assert_eq!(manifest.libraries.len(), 4);
+    }
+
+    #[test]
+    fn keeps_legacy_arguments_when_adding_new_ones() {
+        let mut manifest = manifest(r#"{"minecraftArguments": "--username ${auth_player_name}"}"#);
+        let profile: LoaderProfile = serde_json::from_str(FABRIC_PROFILE).unwrap();
+
+        profile.apply(&mut manifest);
+
+        assert_eq!(manifest.game_arguments().len(), 2);
+        // Default classpath arguments of legacy versions, followed by the loader ones
+        assert_eq!(manifest.jvm_arguments().len(), 4);
+    }
+
+    #[test]
+    fn builds_the_installer_urls() {
+        let sources = LoaderSources::default();
+        let minecraft = VersionId::new("1.20.1");
+        let forge = Loader::Forge {
+            version: "47.2.0".to_string(),
+        };
+        let neoforge = Loader::NeoForge {
+            version: "20.4.80-beta".to_string(),
+        };
+
+        assert_eq!(sources.installer_urls(&forge, &minecraft), vec![
+            "https://maven.minecraftforge.net/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-installer.jar",
+            "https://maven.minecraftforge.net/net/minecraftforge/forge/1.20.1-47.2.0-1.20.1/forge-1.20.1-47.2.0-1.20.1-installer.jar",
+        ]);
+        assert_eq!(sources.installer_urls(&neoforge, &VersionId::new("1.20.4")), vec![
+            "https://maven.neoforged.net/releases/net/neoforged/neoforge/20.4.80-beta/neoforge-20.4.80-beta-installer.jar",
+        ]);
+        assert!(sources
+            .installer_urls(&Loader::Vanilla, &minecraft)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn caches_the_fetched_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("loaders").join("fabric-0.14.21.json");
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/versions/loader/1.20.1/0.14.21/profile/json" => {
+                json(200, serde_json::from_str(FABRIC_PROFILE).unwrap())
+            }
+            _ => (404, vec![]),
+        })
+        .await;
+        let sources = LoaderSources {
+            fabric_meta: server.url.clone(),
+            ..LoaderSources::default()
+        };
+        let loader = Loader::Fabric {
+            version: "0.14.21".to_string(),
+        };
+        let url = sources
+            .profile_url(&loader, &VersionId::new("1.20.1"))
+            .unwrap();
+
+        let profile = fetch_profile(&url, &path).await.unwrap();
+        fetch_profile(&url, &path).await.unwrap();
+
+        assert_eq!(profile.libraries.len(), 2);
+        assert!(profile.ensure_inherits(&VersionId::new("1.20.1")).is_ok());
+        assert!(profile.ensure_inherits(&VersionId::new("1.19.4")).is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fails_on_unknown_loader_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fabric-0.0.0.json");
+        let server = TestServer::start(|_| (400, vec![])).await;
+
+        let result = fetch_profile(
+            &server.url("/versions/loader/1.20.1/0.0.0/profile/json"),
+            &path,
+        )
+        .await;
+
+        assert!(matches!(result, Err(ManagerError::DownloadError(_))));
+        assert!(!path.exists());
+    }
+}
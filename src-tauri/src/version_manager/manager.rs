@@ -1,21 +1,29 @@
 use crate::version_manager::asset::{
-    Argument, ArgumentValue, Artifact, Version, VersionManifest, VersionsManifest,
+    Argument, ArgumentValue, Artifact, AssetIndex, Features, Rule, Version, VersionManifest,
+    VersionsManifest,
 };
+use crate::auth_route::accounts::{AccountKind, MicrosoftIdentity};
+use crate::version_manager::archive;
+use crate::version_manager::authlib::AuthlibInjector;
 use crate::version_manager::errors::ManagerError;
-use crate::version_manager::version::{VersionConstruct, VersionId};
-use crate::{auth_route, Account, Game};
+use crate::version_manager::forge::{ForgeInstaller, ProcessorContext};
+use crate::version_manager::launch::{LaunchContext, LaunchOptions, QuickPlay, CLASSPATH_SEPARATOR};
+use crate::version_manager::loaders::{self, LoaderProfile, LoaderSources};
+use crate::version_manager::version::{VersionConstruct, VersionId, VersionSummary, VersionType};
+use crate::version_manager::instances::{Instance, Loader};
+use crate::Account;
 use futures::StreamExt;
-use regex::Regex;
-use serde_json::Value;
 use sha1::{Digest, Sha1};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 use tauri::api::process::{Command, CommandEvent};
+use zip::ZipArchive;
 
-use tracing::{error, info, instrument, span, warn, Instrument};
+use tracing::{debug, error, info, instrument, span, warn, Instrument};
 
 const VERSION_MANIFEST: &str = "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+const VERSION_MANIFEST_CACHE: &str = "version_manifest.json";
 const MINECRAFT_RESOURCES: &str = "https://resources.download.minecraft.net/";
 
 // We can add clone because at the end is only a PathBuf
@@ -23,7 +31,9 @@ const MINECRAFT_RESOURCES: &str = "https://resources.download.minecraft.net/";
 pub struct AssetManager {
     versions: PathBuf,
     instances: PathBuf,
+    trash: PathBuf,
     common: PathBuf,
+    loader_sources: LoaderSources,
 }
 
 impl AssetManager {
@@ -31,13 +41,79 @@ impl AssetManager {
         AssetManager {
             versions: base.join("versions"),
             instances: base.join("instances"),
+            trash: base.join("trash"),
             common: base.join("common"),
+            loader_sources: LoaderSources::default(),
         }
     }
 
+    pub fn instance_path(&self, id: &str) -> PathBuf {
+        self.instances.join(id)
+    }
+
+    /// Copies the whole directory of an instance, settings included.
+    pub fn copy_instance(&self, from: &str, to: &str) -> Result<(), ManagerError> {
+        let source = self.instance_path(from);
+        let target = self.instance_path(to);
+
+        if target.exists() {
+            return Err(ManagerError::IoError(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} already exists", target),
+            )));
+        }
+
+        if source.exists() {
+            Self::copy_dir(&source, &target)?;
+        } else {
+            fs::create_dir_all(&target)?;
+        }
+
+        Ok(())
+    }
+
+    fn copy_dir(from: &PathBuf, to: &PathBuf) -> io::Result<()> {
+        fs::create_dir_all(to)?;
+
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                Self::copy_dir(&entry.path(), &target)?;
+            } else {
+                fs::copy(entry.path(), target)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the directory of an instance to the trash folder instead of deleting it,
+    /// so it can still be recovered by hand.
+    pub fn trash_instance(&self, id: &str) -> Result<Option<PathBuf>, ManagerError> {
+        let source = self.instance_path(id);
+
+        if !source.exists() {
+            return Ok(None);
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+        let target = self.trash.join(format!("{}-{}", id, timestamp));
+
+        fs::create_dir_all(&self.trash)?;
+        fs::rename(&source, &target)?;
+
+        Ok(Some(target))
+    }
+
     pub fn ensure_exists(&self) -> io::Result<()> {
         fs::create_dir_all(&self.versions)?;
         fs::create_dir_all(&self.instances)?;
+        fs::create_dir_all(&self.trash)?;
         fs::create_dir_all(&self.common)?;
         Ok(())
     }
@@ -51,6 +127,75 @@ impl AssetManager {
         Ok(res.into())
     }
 
+    fn read_cached_version_list(&self) -> Option<VersionsManifest> {
+        let bytes = fs::read(self.common.join(VERSION_MANIFEST_CACHE)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Fetches the version list and caches it, falling back to the cached copy when offline.
+    async fn refresh_version_list(&self) -> Result<VersionsManifest, ManagerError> {
+        match Self::fetch_version_list().await {
+            Ok(versions) => {
+                fs::write(
+                    self.common.join(VERSION_MANIFEST_CACHE),
+                    serde_json::to_vec(&versions)?,
+                )?;
+                Ok(versions)
+            }
+            Err(err) => match self.read_cached_version_list() {
+                Some(versions) => {
+                    warn!("Could not refresh version list, using cached copy: {}", err);
+                    Ok(versions)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Looks the version up in the cached list, which is only refreshed
+    /// when the version is not there yet.
+    async fn find_version(&self, id: &VersionId) -> Result<Version, ManagerError> {
+        let cached = self
+            .read_cached_version_list()
+            .and_then(|versions| versions.versions.into_iter().find(|v| v.id == id.as_str()));
+
+        if let Some(version) = cached {
+            return Ok(version);
+        }
+
+        self.refresh_version_list()
+            .await?
+            .versions
+            .into_iter()
+            .find(|v| v.id == id.as_str())
+            .ok_or_else(|| ManagerError::UnknownVersion(id.to_string()))
+    }
+
+    /// Versions of the given types, newest first. Every type is included when `types` is empty.
+    pub async fn list_versions(
+        &self,
+        types: &[VersionType],
+    ) -> Result<Vec<VersionSummary>, ManagerError> {
+        let mut versions = self
+            .refresh_version_list()
+            .await?
+            .versions
+            .into_iter()
+            .filter(|v| types.is_empty() || types.contains(&v.r#type))
+            .collect::<Vec<_>>();
+
+        versions.sort_by(|a, b| b.release_time.cmp(&a.release_time));
+
+        Ok(versions
+            .into_iter()
+            .map(|v| VersionSummary {
+                id: VersionId::new(&v.id),
+                r#type: v.r#type,
+                release_time: v.release_time,
+            })
+            .collect())
+    }
+
     async fn fetch_version(version: Version) -> Result<Vec<u8>, ManagerError> {
         let res = reqwest::get(version.url).await?.bytes().await?;
 
@@ -71,21 +216,12 @@ impl AssetManager {
             }
 
             false => {
-                let versions = Self::fetch_version_list().await?;
-                let version = versions
-                    .versions
-                    .into_iter()
-                    .find(|v| v.id == version.to_string());
-
-                if let Some(version) = version {
-                    let version = Self::fetch_version(version).await?;
+                let version = self.find_version(version).await?;
+                let version = Self::fetch_version(version).await?;
 
-                    fs::write(version_manifest, &version)?;
+                fs::write(version_manifest, &version)?;
 
-                    Some(version)
-                } else {
-                    None
-                }
+                Some(version)
             }
         };
 
@@ -107,6 +243,9 @@ impl AssetManager {
     ) -> Result<bool, ManagerError> {
         if !path.exists() {
             Ok(true)
+        } else if artifact.sha1.is_empty() {
+            // Loader libraries don't always tell their checksum
+            Ok(false)
         } else {
             let mut file = fs::File::open(&path)?;
 
@@ -153,6 +292,14 @@ impl AssetManager {
             }
         }
 
+        // Files generated while installing a loader can't be downloaded
+        if artifact.url.is_empty() {
+            return match full_path.exists() {
+                true => Ok(()),
+                false => Err(ManagerError::NotFound),
+            };
+        }
+
         if self.should_download_artifact(&full_path, artifact).await? {
             info!("Downloading: {}", artifact.id());
             self.download_file(&full_path, &artifact.url).await?;
@@ -183,21 +330,17 @@ impl AssetManager {
         let libraries = manifest
             .libraries
             .iter()
-            .filter(|lib| {
-                lib.rules.is_none()
-                    || lib
-                        .rules
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .all(|rule| rule.is_valid())
-            })
-            .map(|lib| lib.downloads.artifact.clone())
+            // Library rules only depend on the system
+            .filter(|lib| lib.is_allowed(&Features::default()))
+            .flat_map(|lib| lib.artifact().into_iter().chain(lib.native_artifact().cloned()))
             .collect::<Vec<_>>();
 
         self.review_list_of_artifacts(lib_path.clone(), libraries)
             .await?;
 
+        info!("Extracting natives.");
+        self.extract_natives(version)?;
+
         let asset_index_path = &version.asset_path.join("indexes");
 
         if !asset_index_path.exists() {
@@ -225,6 +368,28 @@ impl AssetManager {
         Ok(())
     }
 
+    /// Extracts the natives of legacy libraries for the current system to the natives directory.
+    fn extract_natives(&self, version: &VersionConstruct) -> Result<(), ManagerError> {
+        let natives = version
+            .manifest
+            .libraries
+            .iter()
+            .filter(|lib| lib.is_allowed(&Features::default()));
+
+        for lib in natives {
+            if let Some(artifact) = lib.native_artifact() {
+                let jar = artifact.derive_path(&version.libraries_path);
+                let extracted = extract_native_jar(&jar, &version.natives_temp_path, |entry| {
+                    lib.excludes(entry)
+                })?;
+
+                info!("Extracted {} natives of {}", extracted, lib.name);
+            }
+        }
+
+        Ok(())
+    }
+
     async fn review_list_of_artifacts(
         &self,
         at: PathBuf,
@@ -258,26 +423,22 @@ impl AssetManager {
         &self,
         version: VersionConstruct,
         minecraft_account: &Account,
-        game: &Game,
+        identity: Option<&MicrosoftIdentity>,
+        injector: Option<&AuthlibInjector>,
+        options: &LaunchOptions,
+        instance: &Instance,
         instance_path: &PathBuf,
-    ) -> Vec<String> {
-        let regex_to_replace = Regex::new(r"\$\{([\S_]+)}").unwrap();
-
-        let class_path_separator = if cfg!(target_os = "windows") {
-            ";"
-        } else {
-            ":"
-        };
+    ) -> Result<Vec<String>, ManagerError> {
+        let features = &options.features;
+        let settings = &options.settings;
 
         let class_path = version
             .manifest
             .libraries
             .iter()
-            .filter(|lib| match &lib.rules {
-                None => true,
-                Some(rules) => rules.iter().all(|x| x.is_valid()),
-            })
-            .filter_map(|lib| match &lib.downloads.artifact.path {
+            .filter(|lib| lib.is_allowed(features))
+            .filter_map(|lib| lib.artifact())
+            .filter_map(|artifact| match &artifact.path {
                 Some(path) => Some(
                     version
                         .libraries_path
@@ -288,24 +449,125 @@ impl AssetManager {
                 ),
                 None => None,
             })
-            .map(|lib| lib + class_path_separator)
+            .map(|lib| lib + CLASSPATH_SEPARATOR)
             .collect::<String>();
 
-        let client_path = version
-            .at
-            .join(version.manifest.downloads.client.file_name());
+        let client_path = version.manifest.downloads.client.derive_path(&version.at);
         let class_path = class_path + client_path.to_str().unwrap();
 
+        let mut context = LaunchContext {
+            auth_player_name: minecraft_account.profile.name.clone(),
+            auth_uuid: minecraft_account.profile.id.clone(),
+            auth_access_token: minecraft_account.mc.access_token.clone(),
+            auth_xuid: identity
+                .and_then(|it| it.xuid.clone())
+                .unwrap_or_default(),
+            clientid: identity
+                .map(|it| it.client_id.clone())
+                .unwrap_or_default(),
+            user_type: match minecraft_account.kind {
+                AccountKind::Microsoft | AccountKind::Yggdrasil => "mojang".to_string(),
+                AccountKind::Offline => "legacy".to_string(),
+            },
+            user_properties: "{}".to_string(),
+            version_name: instance.version.to_string(),
+            version_type: "release".to_string(),
+            game_directory: instance_path.to_str().unwrap().to_string(),
+            assets_root: version.asset_path.to_str().unwrap().to_string(),
+            game_assets: version.game_assets.to_str().unwrap().to_string(),
+            assets_index_name: version.manifest.asset_index.id.clone().unwrap_or_default(),
+            natives_directory: version.natives_temp_path.to_str().unwrap().to_string(),
+            classpath: class_path,
+            library_directory: version.libraries_path.to_str().unwrap().to_string(),
+            classpath_separator: CLASSPATH_SEPARATOR.to_string(),
+            launcher_name: "Cognatize".to_string(),
+            launcher_version: "1.0.0".to_string(),
+            resolution_width: settings
+                .resolution
+                .map(|it| it.width.to_string())
+                .unwrap_or_default(),
+            resolution_height: settings
+                .resolution
+                .map(|it| it.height.to_string())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        match &options.quick_play {
+            Some(QuickPlay::Singleplayer { world }) => context.quick_play_singleplayer = world.clone(),
+            Some(QuickPlay::Multiplayer { address }) => context.quick_play_multiplayer = address.clone(),
+            Some(QuickPlay::Realms { realm }) => context.quick_play_realms = realm.clone(),
+            None => {}
+        }
+
+        // The agent has to be loaded before the game classes
+        let injector_arguments = injector
+            .map(|injector| injector.jvm_arguments())
+            .unwrap_or_default()
+            .into_iter()
+            .map(Argument::Plain)
+            .collect::<Vec<_>>();
+
+        let mut settings_arguments = vec![];
+        if let Some(min_memory) = settings.min_memory {
+            settings_arguments.push(format!("-Xms{}M", min_memory));
+        }
+        if let Some(max_memory) = settings.max_memory {
+            settings_arguments.push(format!("-Xmx{}M", max_memory));
+        }
+        settings_arguments.extend(settings.jvm_arguments.clone().unwrap_or_default());
+
+        let settings_arguments = settings_arguments
+            .into_iter()
+            .map(Argument::Plain)
+            .collect::<Vec<_>>();
+
+        let jvm_arguments = version.manifest.jvm_arguments();
+        let mut game_arguments = version.manifest.game_arguments();
+
+        // Legacy versions have no rules asking for the resolution
+        if !version.manifest.supports_custom_resolution() && settings.resolution.is_some() {
+            game_arguments.extend(
+                ["--width", "${resolution_width}", "--height", "${resolution_height}"]
+                    .iter()
+                    .map(|arg| Argument::Plain(arg.to_string())),
+            );
+        }
+
+        // Older versions can only join servers, through the legacy arguments
+        if !version.manifest.supports_quick_play() {
+            match &options.quick_play {
+                Some(QuickPlay::Multiplayer { address }) => {
+                    let (host, port) = QuickPlay::split_address(address);
+                    game_arguments.extend(
+                        vec!["--server".to_string(), host, "--port".to_string(), port.to_string()]
+                            .into_iter()
+                            .map(Argument::Plain),
+                    );
+                }
+                Some(quick_play) => {
+                    warn!("Version {} doesn't support {:?}", version.id, quick_play)
+                }
+                None => {}
+            }
+        }
+
+        if settings.fullscreen.unwrap_or(false) {
+            game_arguments.push(Argument::Plain("--fullscreen".to_string()));
+        }
+
         // First we set the jvm parameters
-        let arguments = Vec::new()
+        let templates = Vec::new()
             .iter()
-            .chain(&version.manifest.arguments.jvm)
+            .chain(&jvm_arguments)
+            .chain(&injector_arguments)
+            .chain(&settings_arguments)
             .chain(&vec![Argument::Plain(version.manifest.main_class.clone())])
-            .chain(&version.manifest.arguments.game)
+            .chain(&game_arguments)
             .flat_map(|arg| match arg {
                 Argument::Plain(value) => vec![value.clone()],
                 Argument::WithRules { value, rules } => {
-                    if rules.iter().any(|rule| !rule.is_valid()) {
+                    if !Rule::allows(rules, features) {
                         vec![]
                     } else {
                         match value {
@@ -315,74 +577,53 @@ impl AssetManager {
                     }
                 }
             })
-            .map(|arg| {
-                let group = regex_to_replace.captures(&arg);
-
-                if let Some(group) = group {
-                    let key = group.get(1).unwrap();
-                    let replacement = match key.as_str() {
-                        "auth_player_name" => minecraft_account.profile.name.clone(),
-                        "version_name" => game.version.to_string(),
-                        "game_directory" => instance_path.to_str().unwrap().to_string(),
-                        "assets_root" => version.asset_path.to_str().unwrap().to_string(),
-                        "assets_index_name" => {
-                            version.manifest.asset_index.id.as_ref().unwrap().clone()
-                        }
-                        "auth_uuid" => minecraft_account.profile.id.clone(),
-                        "auth_access_token" => minecraft_account.mc.access_token.clone(),
-                        "clientid" => "".to_string(),
-                        "auth_xuid" => "".to_string(),
-                        "user_type" => "mojang".to_string(),
-                        "version_type" => "release".to_string(),
-                        "natives_directory" => {
-                            version.natives_temp_path.to_str().unwrap().to_string()
-                        }
-                        "launcher_name" => "Cognatize".to_string(),
-                        "launcher_version" => "1.0.0".to_string(),
-                        "classpath" => class_path.clone(),
-                        _ => key.as_str().to_string(),
-                    };
-
-                    regex_to_replace.replace(&arg, &replacement).to_string()
-                } else {
-                    arg
-                }
-            })
             .collect::<Vec<_>>();
 
-        println!("main class: {}", version.manifest.main_class);
-        println!("{:?}", arguments.join(" "));
+        let arguments = templates
+            .iter()
+            .map(|arg| context.substitute(arg, options.strict_arguments))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        arguments
+        // The access token must never reach the logs
+        let redacted = context.redacted();
+        let printable = templates
+            .iter()
+            .map(|arg| redacted.substitute(arg, false))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        info!("Main class: {}", version.manifest.main_class);
+        debug!("Arguments: {}", printable.join(" "));
+
+        Ok(arguments)
     }
 
-    fn get_object_artifacts(
-        &self,
-        version: &VersionConstruct,
-    ) -> Result<Vec<Artifact>, ManagerError> {
+    fn read_asset_index(&self, version: &VersionConstruct) -> Result<AssetIndex, ManagerError> {
         let file_name = version.manifest.asset_index.file_name();
         let index_file = version.asset_path.join("indexes").join(file_name);
         let load_file = fs::read(&index_file)?;
-        let file = serde_json::from_slice::<Value>(&load_file)?;
 
-        let objects = file["objects"].as_object().unwrap();
+        Ok(serde_json::from_slice::<AssetIndex>(&load_file)?)
+    }
 
-        // convert the objects to a list of artifacts
-        let artifacts = objects
-            .iter()
-            .map(|(key, value)| {
-                let hash = value.get("hash").unwrap().as_str().unwrap();
-                let sub = hash.chars().take(2).collect::<String>();
-                let size = value.get("size").unwrap().as_u64().unwrap();
+    fn get_object_artifacts(
+        &self,
+        version: &VersionConstruct,
+    ) -> Result<Vec<Artifact>, ManagerError> {
+        let index = self.read_asset_index(version)?;
 
-                let path = sub + "/" + hash;
+        // convert the objects to a list of artifacts
+        let artifacts = index
+            .objects
+            .into_iter()
+            .map(|(key, object)| {
+                let path = object.path();
                 let url = MINECRAFT_RESOURCES.to_string() + path.as_str();
 
                 Artifact {
-                    id: Some(key.clone()),
+                    id: Some(key),
                     path: Some(path),
-                    sha1: hash.to_string(),
-                    size,
+                    sha1: object.hash,
+                    size: object.size,
                     url,
                 }
             })
@@ -391,60 +632,78 @@ impl AssetManager {
         Ok(artifacts)
     }
 
-    async fn check_auth(&self, auth: &Account) -> Result<(), ManagerError> {
-        let start = std::time::SystemTime::now();
-        let now = start
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
+    /// Old asset indexes expect the objects by name instead of by hash, so the named
+    /// tree is rebuilt from the (already verified) object store.
+    /// Returns the folder the game has to be pointed at.
+    fn reconstruct_assets(
+        &self,
+        version: &VersionConstruct,
+        instance_path: &PathBuf,
+    ) -> Result<PathBuf, ManagerError> {
+        let index = self.read_asset_index(version)?;
+
+        let target = if index.map_to_resources {
+            instance_path.join("resources")
+        } else if index.is_virtual {
+            let index_name = version.manifest.asset_index.id.as_deref().unwrap_or("legacy");
+            version.asset_path.join("virtual").join(index_name)
+        } else {
+            return Ok(version.asset_path.clone());
+        };
 
-        if now >= auth.auth_exp_time {
-            auth_route::auther::refresh_oauth(&auth.auth).await?;
-        }
+        info!("Reconstructing assets in {:?}", target);
+        let objects_path = version.asset_path.join("objects");
+
+        for (name, object) in index.objects.iter() {
+            let destination = target.join(name);
 
-        if now >= auth.mc_exp_time {
-            auth_route::auther::auth_minecraft_token(&auth.auth).await?;
+            let up_to_date = fs::metadata(&destination)
+                .map_or(false, |metadata| metadata.len() == object.size);
+
+            if up_to_date {
+                continue;
+            }
+
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::copy(objects_path.join(object.path()), &destination)?;
         }
 
-        Ok(())
+        Ok(target)
     }
 
-    pub async fn load_version(
+    /// Downloads whatever is missing of the version: manifest, libraries and assets,
+    /// then installs the loader on top of it.
+    pub async fn install_version(
         &self,
-        game: Game,
-        minecraft_account: Account,
-    ) -> Result<(), ManagerError> {
-        let version = game.version;
-        // Each game would contain a list of mods that need to be loaded across with resources.
-
-        info!(
-            "Starting game {} ({}) with account {} and version {}",
-            &game.name,
-            &game.id,
-            &minecraft_account.profile.name,
-            version.to_string()
-        );
+        version: &VersionId,
+        loader: &Loader,
+    ) -> Result<VersionConstruct, ManagerError> {
         let version_path = self.versions.join(&version.to_string());
 
         if !version_path.exists() {
             fs::create_dir(&version_path)?;
         }
 
-        info!("Auth check");
-        self.check_auth(&minecraft_account).await?;
-
         info!("Loading version {}", version.to_string());
-        let manifest = self.get_manifest(&version_path, &version).await?;
+        let mut manifest = self.get_manifest(&version_path, version).await?;
+
+        // Named after the version, which modern Forge expects to find on the classpath
+        manifest.downloads.client.path = Some(format!("{}.jar", version));
 
         let asset_path = self.common.join("assets");
         let libraries_path = self.common.join("libs");
         let natives_temp_path = version_path.join("natives");
 
-        // Version construct will include everything related to the version
-        let construct = VersionConstruct {
-            id: version,
+        // Version construct will include everything related to the version,
+        // until the asset index is checked we assume the current assets layout
+        let mut construct = VersionConstruct {
+            id: version.clone(),
             at: version_path,
             manifest,
+            game_assets: asset_path.clone(),
             asset_path,
             libraries_path,
             natives_temp_path,
@@ -453,21 +712,158 @@ impl AssetManager {
         info!("Checking version...");
         self.check_version(&construct).await?;
 
-        let instance_path = self.instances.join(&game.id);
+        if let Some(profile) = self.install_loader(&construct, loader).await? {
+            info!("Applying loader profile {}", profile.id);
+            let libraries = profile
+                .libraries
+                .iter()
+                .filter(|lib| lib.is_allowed(&Features::default()))
+                .filter_map(|lib| lib.artifact())
+                .collect::<Vec<_>>();
+
+            info!("Performing loader libraries check.");
+            self.review_list_of_artifacts(construct.libraries_path.clone(), libraries)
+                .await?;
+
+            profile.apply(&mut construct.manifest);
+        }
+
+        Ok(construct)
+    }
+
+    /// Installs the loader for the version unless it already is, and returns its profile.
+    /// Profiles are kept in `versions/<version>/loaders` once the loader is installed.
+    async fn install_loader(
+        &self,
+        construct: &VersionConstruct,
+        loader: &Loader,
+    ) -> Result<Option<LoaderProfile>, ManagerError> {
+        let name = match loaders::loader_name(loader) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        info!("Installing loader {}", name);
+        let at = construct.at.join("loaders");
+        let cached = at.join(format!("{}.json", name));
+
+        let profile = match self.loader_sources.profile_url(loader, &construct.id) {
+            Some(url) => loaders::fetch_profile(&url, &cached).await?,
+            None => match LoaderProfile::read(&cached)? {
+                Some(profile) => profile,
+                None => {
+                    let profile = self.run_installer(construct, loader, &at, &name).await?;
+                    profile.write(&cached)?;
+                    profile
+                }
+            },
+        };
+
+        profile.ensure_inherits(&construct.id)?;
+
+        Ok(Some(profile))
+    }
+
+    /// Runs the Forge or NeoForge installer, which patches the client for the loader.
+    async fn run_installer(
+        &self,
+        construct: &VersionConstruct,
+        loader: &Loader,
+        at: &Path,
+        name: &str,
+    ) -> Result<LoaderProfile, ManagerError> {
+        let urls = self.loader_sources.installer_urls(loader, &construct.id);
+        let path = at.join(format!("{}-installer.jar", name));
+        let installer = ForgeInstaller::download(&urls, &path).await?;
+
+        info!("Extracting the libraries of the installer");
+        installer.extract_libraries(&construct.libraries_path)?;
+
+        info!("Performing installer libraries check.");
+        self.review_list_of_artifacts(construct.libraries_path.clone(), installer.libraries())
+            .await?;
+
+        let client_jar = construct.manifest.downloads.client.derive_path(&construct.at);
+        let work = at.join(name);
+        let context = ProcessorContext {
+            minecraft: &construct.id,
+            client_jar: &client_jar,
+            libraries: &construct.libraries_path,
+            root: self.versions.parent().unwrap_or(&self.versions),
+            work: &work,
+        };
+
+        installer.run_processors(&context).await?;
+
+        if work.exists() {
+            fs::remove_dir_all(&work)?;
+        }
+
+        Ok(installer.profile().clone())
+    }
+
+    pub async fn load_version(
+        &self,
+        instance: Instance,
+        minecraft_account: Account,
+        identity: Option<MicrosoftIdentity>,
+        options: LaunchOptions,
+    ) -> Result<(), ManagerError> {
+        let version = instance.version.clone();
+
+        info!(
+            "Starting instance {} ({}) with account {} and version {}",
+            &instance.name,
+            &instance.id,
+            &minecraft_account.profile.name,
+            version.to_string()
+        );
+
+        let mut construct = self.install_version(&version, &instance.loader).await?;
+
+        let instance_path = self.instance_path(&instance.id);
 
         if !instance_path.exists() {
             fs::create_dir(&instance_path)?;
         }
 
+        let injector = match (&minecraft_account.kind, &minecraft_account.server) {
+            (AccountKind::Yggdrasil, Some(server)) => {
+                info!("Preparing authlib-injector...");
+                let at = self.common.join("authlib-injector");
+                Some(AuthlibInjector::prepare(&at, server).await?)
+            }
+            _ => None,
+        };
+
+        construct.game_assets = self.reconstruct_assets(&construct, &instance_path)?;
+
         info!("Loading arguments...");
-        let args = self.build_arguments(construct, &minecraft_account, &game, &instance_path);
+        let args = self.build_arguments(
+            construct,
+            &minecraft_account,
+            identity.as_ref(),
+            injector.as_ref(),
+            &options,
+            &instance,
+            &instance_path,
+        )?;
+
+        // The wrapper receives the java command as its arguments
+        let mut command = options.settings.wrapper_command();
+        command.push("java".to_string());
+        command.extend(args);
+
+        let program = command.remove(0);
+        let environment = options.settings.environment.clone();
 
         info!("Initializing Minecraft process...");
         let handle = tokio::spawn(
             async move {
-                info!("Running Minecraft");
-                let (mut rx, _) = Command::new("java")
-                    .args(args)
+                info!("Running Minecraft through {}", program);
+                let (mut rx, _) = Command::new(program)
+                    .args(command)
+                    .envs(environment)
                     .current_dir(instance_path)
                     .spawn()
                     .expect("Failed to start java");
@@ -486,8 +882,8 @@ impl AssetManager {
             }
             .instrument(tracing::info_span!(
                 "minecraft",
-                game = &game.id,
-                version = &game.version.to_string(),
+                instance = &instance.id,
+                version = &instance.version.to_string(),
                 user_id = &minecraft_account.profile.id
             )),
         );
@@ -497,3 +893,66 @@ impl AssetManager {
         Ok(())
     }
 }
+
+/// Extracts the files of the jar to `to`, except the entries `excludes` matches.
+/// Returns how many files were extracted.
+fn extract_native_jar<F>(jar: &Path, to: &Path, excludes: F) -> Result<usize, ManagerError>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut zip = ZipArchive::new(fs::File::open(jar)?)?;
+    let mut extracted = 0;
+
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+
+        if entry.is_dir() || excludes(entry.name()) {
+            continue;
+        }
+
+        let path = match entry.enclosed_name() {
+            Some(path) if archive::is_enclosed(path) => to.join(path),
+            _ => continue,
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        io::copy(&mut entry, &mut fs::File::create(&path)?)?;
+        extracted += 1;
+    }
+
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn extracts_natives_except_excluded_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = dir.path().join("natives.jar");
+        let to = dir.path().join("natives");
+
+        let mut zip = ZipWriter::new(fs::File::create(&jar).unwrap());
+        for name in ["liblwjgl64.so", "META-INF/MANIFEST.MF", "linux/libopenal64.so", "../escape.so"] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.add_directory("META-INF/", FileOptions::default()).unwrap();
+        zip.finish().unwrap();
+
+        let extracted =
+            extract_native_jar(&jar, &to, |entry| entry.starts_with("META-INF/")).unwrap();
+
+        assert_eq!(extracted, 2);
+        assert_eq!(fs::read(to.join("liblwjgl64.so")).unwrap(), b"liblwjgl64.so");
+        assert!(to.join("linux").join("libopenal64.so").exists());
+        assert!(!to.join("META-INF").exists());
+        assert!(!dir.path().join("escape.so").exists());
+    }
+}
@@ -1,5 +1,14 @@
+pub mod archive;
 mod asset;
+mod authlib;
+mod forge;
+mod loaders;
 pub mod manager;
-mod errors;
+pub mod errors;
 pub mod version;
-pub mod games;
\ No newline at end of file
+pub mod games;
+pub mod instances;
+pub mod launch;
+pub mod mrpack;
+pub mod curseforge;
+pub mod mods;
\ No newline at end of file
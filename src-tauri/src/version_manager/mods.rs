@@ -0,0 +1,727 @@
+use crate::version_manager::errors::ManagerError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+use zip::ZipArchive;
+
+const MODS_FOLDER: &str = "mods";
+const DISABLED_EXTENSION: &str = ".disabled";
+/// Jars bundled by Forge and NeoForge mods, described by `metadata.json` when present.
+const JARJAR_FOLDER: &str = "META-INF/jarjar/";
+const JARJAR_METADATA: &str = "META-INF/jarjar/metadata.json";
+/// Jars can bundle jars themselves, up to this depth.
+const MAX_NESTING: usize = 3;
+/// Dependencies provided by the game or the loaders, not by mods.
+const PLATFORM_IDS: &[&str] = &[
+    "minecraft",
+    "java",
+    "fabricloader",
+    "quilt_loader",
+    "forge",
+    "neoforge",
+    "javafml",
+];
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModFormat {
+    Fabric,
+    Quilt,
+    Forge,
+    NeoForge,
+    /// A jar without metadata we understand.
+    Unknown,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ModDependency {
+    pub id: String,
+    /// Version range, in the syntax of the loader.
+    pub version: Option<String>,
+    pub required: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ModInfo {
+    pub file_name: String,
+    pub enabled: bool,
+    pub format: ModFormat,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Other ids the mod answers to, including the rest of the mods in the same jar.
+    pub provides: Vec<String>,
+    pub dependencies: Vec<ModDependency>,
+    /// Data uri of the icon.
+    pub icon: Option<String>,
+}
+
+impl ModInfo {
+    fn unknown(file_name: String, enabled: bool) -> ModInfo {
+        ModInfo {
+            file_name,
+            enabled,
+            format: ModFormat::Unknown,
+            id: None,
+            name: None,
+            version: None,
+            provides: vec![],
+            dependencies: vec![],
+            icon: None,
+        }
+    }
+
+    fn ids(&self) -> impl Iterator<Item = &String> {
+        self.id.iter().chain(&self.provides)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DuplicateMod {
+    pub id: String,
+    pub files: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MissingDependency {
+    pub file_name: String,
+    pub dependency: String,
+    pub version: Option<String>,
+}
+
+/// Mods of an instance, with the problems found among the enabled ones.
+#[derive(Serialize, Clone, Debug)]
+pub struct ModReport {
+    pub mods: Vec<ModInfo>,
+    pub duplicates: Vec<DuplicateMod>,
+    pub missing_dependencies: Vec<MissingDependency>,
+}
+
+impl ModReport {
+    pub fn new(mods: Vec<ModInfo>) -> ModReport {
+        let enabled = mods.iter().filter(|it| it.enabled).collect::<Vec<_>>();
+
+        let mut files_by_id: HashMap<&String, Vec<String>> = HashMap::new();
+        for info in &enabled {
+            if let Some(id) = &info.id {
+                files_by_id.entry(id).or_default().push(info.file_name.clone());
+            }
+        }
+
+        let mut duplicates = files_by_id
+            .into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(id, files)| DuplicateMod {
+                id: id.clone(),
+                files,
+            })
+            .collect::<Vec<_>>();
+        duplicates.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let available = enabled
+            .iter()
+            .flat_map(|it| it.ids())
+            .map(|id| id.as_str())
+            .chain(PLATFORM_IDS.iter().copied())
+            .collect::<Vec<_>>();
+
+        let missing_dependencies = enabled
+            .iter()
+            .flat_map(|info| {
+                info.dependencies
+                    .iter()
+                    .filter(|dependency| dependency.required)
+                    .filter(|dependency| !available.contains(&dependency.id.as_str()))
+                    .map(move |dependency| MissingDependency {
+                        file_name: info.file_name.clone(),
+                        dependency: dependency.id.clone(),
+                        version: dependency.version.clone(),
+                    })
+            })
+            .collect();
+
+        ModReport {
+            mods,
+            duplicates,
+            missing_dependencies,
+        }
+    }
+}
+
+pub fn mods_path(instance_path: &Path) -> PathBuf {
+    instance_path.join(MODS_FOLDER)
+}
+
+/// Reads the metadata of every jar in the mods folder, disabled ones included.
+pub fn list(instance_path: &Path) -> Result<Vec<ModInfo>, ManagerError> {
+    let folder = mods_path(instance_path);
+    if !folder.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut mods = vec![];
+
+    for entry in fs::read_dir(&folder)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        let file_name = match entry.file_name().into_string() {
+            Ok(file_name) => file_name,
+            Err(_) => continue,
+        };
+
+        let (jar_name, enabled) = match file_name.strip_suffix(DISABLED_EXTENSION) {
+            Some(jar_name) => (jar_name, false),
+            None => (file_name.as_str(), true),
+        };
+        if !jar_name.ends_with(".jar") {
+            continue;
+        }
+
+        // A broken jar is still listed, so it can be found and removed
+        let info = read_jar(&entry.path(), file_name.clone(), enabled).unwrap_or_else(|err| {
+            warn!("Could not read {}: {}", file_name, err);
+            ModInfo::unknown(file_name.clone(), enabled)
+        });
+
+        mods.push(info);
+    }
+
+    mods.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    Ok(mods)
+}
+
+/// Renames the jar to or from `.disabled`, returning its new file name.
+pub fn set_enabled(instance_path: &Path, file_name: &str, enabled: bool) -> Result<String, ManagerError> {
+    let mut components = Path::new(file_name).components();
+    let is_file_name = matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none();
+
+    if !is_file_name {
+        return Err(ManagerError::UnknownMod(file_name.to_string()));
+    }
+
+    // Same files as the ones `list` reports
+    let jar_name = file_name.strip_suffix(DISABLED_EXTENSION).unwrap_or(file_name);
+    if !jar_name.ends_with(".jar") {
+        return Err(ManagerError::UnknownMod(file_name.to_string()));
+    }
+
+    let folder = mods_path(instance_path);
+    let source = folder.join(file_name);
+    if !source.is_file() {
+        return Err(ManagerError::UnknownMod(file_name.to_string()));
+    }
+    let target = match enabled {
+        true => jar_name.to_string(),
+        false => format!("{}{}", jar_name, DISABLED_EXTENSION),
+    };
+
+    if target != file_name {
+        let target_path = folder.join(&target);
+
+        if target_path.exists() {
+            return Err(ManagerError::IoError(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", target),
+            )));
+        }
+
+        fs::rename(&source, target_path)?;
+    }
+
+    Ok(target)
+}
+
+fn read_jar(path: &Path, file_name: String, enabled: bool) -> Result<ModInfo, ManagerError> {
+    let mut zip = ZipArchive::new(fs::File::open(path)?)?;
+    let mut info = read_archive(&mut zip, file_name, enabled, 0)?;
+
+    info.icon = info
+        .icon
+        .take()
+        .and_then(|icon| read_entry(&mut zip, icon.trim_start_matches('/')))
+        .map(|bytes| format!("data:image/png;base64,{}", STANDARD.encode(bytes)));
+
+    Ok(info)
+}
+
+fn read_archive<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    file_name: String,
+    enabled: bool,
+    depth: usize,
+) -> Result<ModInfo, ManagerError> {
+    let mut info = if let Some(json) = read_entry(zip, "quilt.mod.json") {
+        read_quilt(&serde_json::from_slice(&json)?, file_name, enabled)
+    } else if let Some(json) = read_entry(zip, "fabric.mod.json") {
+        read_fabric(&serde_json::from_slice(&json)?, file_name, enabled)
+    } else if let Some(toml) = read_entry(zip, "META-INF/neoforge.mods.toml") {
+        read_mods_toml(&toml, ModFormat::NeoForge, file_name, enabled)?
+    } else if let Some(toml) = read_entry(zip, "META-INF/mods.toml") {
+        read_mods_toml(&toml, ModFormat::Forge, file_name, enabled)?
+    } else {
+        return Ok(ModInfo::unknown(file_name, enabled));
+    };
+
+    // Forge fills the version from the manifest of the jar at runtime
+    if info.version.as_deref() == Some("${file.jarVersion}") {
+        info.version = read_entry(zip, "META-INF/MANIFEST.MF")
+            .and_then(|manifest| manifest_value(&manifest, "Implementation-Version"));
+    }
+
+    if depth < MAX_NESTING {
+        for path in nested_jars(zip, info.format) {
+            let nested = match read_entry(zip, &path) {
+                Some(bytes) => ZipArchive::new(Cursor::new(bytes))
+                    .map_err(ManagerError::from)
+                    .and_then(|mut zip| read_archive(&mut zip, path.clone(), enabled, depth + 1)),
+                None => continue,
+            };
+
+            // The mods bundled in the jar are loaded along with it
+            match nested {
+                Ok(nested) => {
+                    for id in nested.ids() {
+                        if info.id.as_ref() != Some(id) && !info.provides.contains(id) {
+                            info.provides.push(id.clone());
+                        }
+                    }
+                }
+                Err(err) => warn!("Could not read {} inside {}: {}", path, info.file_name, err),
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+#[derive(Deserialize, Debug)]
+struct JarJarMetadata {
+    jars: Vec<JarJarEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct JarJarEntry {
+    path: String,
+}
+
+/// Paths of the jars bundled inside the jar, as declared by its loader.
+fn nested_jars<R: Read + Seek>(zip: &mut ZipArchive<R>, format: ModFormat) -> Vec<String> {
+    let json = |zip: &mut ZipArchive<R>, name: &str| {
+        read_entry(zip, name).and_then(|json| serde_json::from_slice::<Value>(&json).ok())
+    };
+
+    match format {
+        ModFormat::Fabric => json(zip, "fabric.mod.json")
+            .and_then(|json| json.get("jars").and_then(|it| it.as_array()).cloned())
+            .map(|jars| jars.iter().filter_map(|jar| string(jar, "file")).collect())
+            .unwrap_or_default(),
+        ModFormat::Quilt => json(zip, "quilt.mod.json")
+            .and_then(|json| json.pointer("/quilt_loader/jars").cloned())
+            .and_then(|jars| jars.as_array().cloned())
+            .map(|jars| jars.iter().filter_map(|jar| jar.as_str().map(String::from)).collect())
+            .unwrap_or_default(),
+        ModFormat::Forge | ModFormat::NeoForge => {
+            let metadata = read_entry(zip, JARJAR_METADATA)
+                .and_then(|json| serde_json::from_slice::<JarJarMetadata>(&json).ok());
+
+            match metadata {
+                Some(metadata) => metadata.jars.into_iter().map(|jar| jar.path).collect(),
+                None => zip
+                    .file_names()
+                    .filter(|name| {
+                        name.strip_prefix(JARJAR_FOLDER)
+                            .map_or(false, |name| name.ends_with(".jar") && !name.contains('/'))
+                    })
+                    .map(String::from)
+                    .collect(),
+            }
+        }
+        ModFormat::Unknown => vec![],
+    }
+}
+
+fn read_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut file = zip.by_name(name).ok()?;
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes).ok()?;
+
+    Some(bytes)
+}
+
+pub(crate) fn manifest_value(manifest: &[u8], key: &str) -> Option<String> {
+    String::from_utf8_lossy(manifest)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim() == key)
+        .map(|(_, value)| value.trim().to_string())
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|it| it.as_str()).map(String::from)
+}
+
+/// Icons are either a path, or paths by size of which we take the largest.
+fn json_icon(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(path) => Some(path.clone()),
+        Value::Object(sizes) => sizes
+            .iter()
+            .max_by_key(|(size, _)| size.parse::<u32>().unwrap_or(0))
+            .and_then(|(_, path)| path.as_str().map(String::from)),
+        _ => None,
+    }
+}
+
+fn read_fabric(json: &Value, file_name: String, enabled: bool) -> ModInfo {
+    let dependencies = json
+        .get("depends")
+        .and_then(|it| it.as_object())
+        .map(|depends| {
+            depends
+                .iter()
+                .map(|(id, version)| ModDependency {
+                    id: id.clone(),
+                    version: match version {
+                        Value::String(version) => Some(version.clone()),
+                        Value::Array(versions) => Some(
+                            versions
+                                .iter()
+                                .filter_map(|it| it.as_str())
+                                .collect::<Vec<_>>()
+                                .join(" || "),
+                        ),
+                        _ => None,
+                    },
+                    required: true,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ModInfo {
+        file_name,
+        enabled,
+        format: ModFormat::Fabric,
+        id: string(json, "id"),
+        name: string(json, "name"),
+        version: string(json, "version"),
+        provides: json
+            .get("provides")
+            .and_then(|it| it.as_array())
+            .map(|ids| ids.iter().filter_map(|it| it.as_str()).map(String::from).collect())
+            .unwrap_or_default(),
+        dependencies,
+        icon: json_icon(json.get("icon")),
+    }
+}
+
+fn read_quilt(json: &Value, file_name: String, enabled: bool) -> ModInfo {
+    let loader = json.get("quilt_loader").cloned().unwrap_or(Value::Null);
+    let metadata = loader.get("metadata").cloned().unwrap_or(Value::Null);
+
+    let ids = |key: &str| -> Vec<&Value> {
+        loader
+            .get(key)
+            .and_then(|it| it.as_array())
+            .map(|it| it.iter().collect())
+            .unwrap_or_default()
+    };
+
+    // Entries are either an id or an object describing the dependency
+    let dependencies = ids("depends")
+        .into_iter()
+        .filter_map(|dependency| match dependency {
+            Value::String(id) => Some(ModDependency {
+                id: id.clone(),
+                version: None,
+                required: true,
+            }),
+            Value::Object(_) => Some(ModDependency {
+                id: string(dependency, "id")?,
+                version: string(dependency, "versions"),
+                required: !dependency
+                    .get("optional")
+                    .and_then(|it| it.as_bool())
+                    .unwrap_or(false),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let provides = ids("provides")
+        .into_iter()
+        .filter_map(|provided| match provided {
+            Value::String(id) => Some(id.clone()),
+            _ => string(provided, "id"),
+        })
+        .collect();
+
+    ModInfo {
+        file_name,
+        enabled,
+        format: ModFormat::Quilt,
+        id: string(&loader, "id"),
+        name: string(&metadata, "name"),
+        version: string(&loader, "version"),
+        provides,
+        dependencies,
+        icon: json_icon(metadata.get("icon")),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ModsToml {
+    #[serde(default)]
+    mods: Vec<TomlMod>,
+    #[serde(default)]
+    dependencies: HashMap<String, Vec<TomlDependency>>,
+    logo_file: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TomlMod {
+    mod_id: String,
+    version: Option<String>,
+    display_name: Option<String>,
+    logo_file: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TomlDependency {
+    mod_id: String,
+    /// Forge only.
+    mandatory: Option<bool>,
+    /// NeoForge only, `required`, `optional`, `incompatible` or `discouraged`.
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    version_range: Option<String>,
+}
+
+fn read_mods_toml(
+    toml: &[u8],
+    format: ModFormat,
+    file_name: String,
+    enabled: bool,
+) -> Result<ModInfo, ManagerError> {
+    let toml = String::from_utf8_lossy(toml);
+    let mut contents: ModsToml = toml::from_str(&toml)
+        .map_err(|err| ManagerError::UnsupportedArchive(format!("{}: {}", file_name, err)))?;
+
+    if contents.mods.is_empty() {
+        return Ok(ModInfo::unknown(file_name, enabled));
+    }
+
+    let main = contents.mods.remove(0);
+    let dependencies = contents
+        .dependencies
+        .remove(&main.mod_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|dependency| {
+            !matches!(dependency.kind.as_deref(), Some("incompatible") | Some("discouraged"))
+        })
+        .map(|dependency| ModDependency {
+            required: dependency
+                .mandatory
+                .unwrap_or_else(|| dependency.kind.as_deref().map_or(true, |kind| kind == "required")),
+            id: dependency.mod_id,
+            version: dependency.version_range,
+        })
+        .collect();
+
+    Ok(ModInfo {
+        file_name,
+        enabled,
+        format,
+        id: Some(main.mod_id),
+        name: main.display_name,
+        version: main.version,
+        provides: contents.mods.into_iter().map(|it| it.mod_id).collect(),
+        dependencies,
+        icon: main.logo_file.or(contents.logo_file),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn jar<S: AsRef<str>>(entries: &[(S, Vec<u8>)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+
+        for (name, contents) in entries {
+            zip.start_file(name.as_ref(), FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Fabric mod bundling the given jars in `META-INF/jars`.
+    fn fabric_jar(id: &str, depends: &[&str], nested: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+        let nested = nested
+            .into_iter()
+            .map(|(name, contents)| (format!("META-INF/jars/{}", name), contents))
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "schemaVersion": 1,
+            "id": id,
+            "version": "1.0.0",
+            "depends": depends
+                .iter()
+                .map(|id| (id.to_string(), serde_json::json!("*")))
+                .collect::<serde_json::Map<_, _>>(),
+            "jars": nested
+                .iter()
+                .map(|(path, _)| serde_json::json!({ "file": path }))
+                .collect::<Vec<_>>(),
+        });
+
+        let mut entries = vec![("fabric.mod.json".to_string(), json.to_string().into_bytes())];
+        entries.extend(nested);
+
+        jar(&entries)
+    }
+
+    fn mods_toml(id: &str) -> Vec<u8> {
+        let toml = r#"
+            modLoader = "javafml"
+            loaderVersion = "[47,)"
+            license = "MIT"
+
+            [[mods]]
+            modId = "{id}"
+            version = "1.0.0"
+        "#;
+
+        toml.replace("{id}", id).into_bytes()
+    }
+
+    fn write_mod(instance: &Path, file_name: &str, contents: Vec<u8>) {
+        let folder = mods_path(instance);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join(file_name), contents).unwrap();
+    }
+
+    #[test]
+    fn provides_the_mods_nested_in_fabric_jars() {
+        let dir = tempfile::tempdir().unwrap();
+        let networking = fabric_jar("fabric-networking-api-v1", &[], vec![]);
+        let base = fabric_jar("fabric-api-base", &[], vec![("networking.jar", networking)]);
+        let api = fabric_jar("fabric-api", &[], vec![("base.jar", base)]);
+        let sodium = fabric_jar("sodium", &["fabric-networking-api-v1"], vec![]);
+        write_mod(dir.path(), "fabric-api.jar", api);
+        write_mod(dir.path(), "sodium.jar", sodium);
+
+        let report = ModReport::new(list(dir.path()).unwrap());
+
+        assert_eq!(report.mods[0].provides, ["fabric-api-base", "fabric-networking-api-v1"]);
+        assert!(report.missing_dependencies.is_empty());
+    }
+
+    #[test]
+    fn provides_the_mods_nested_in_forge_jars() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = serde_json::json!({
+            "jars": [{
+                "identifier": {
+                    "group": "software.bernie.geckolib",
+                    "artifact": "geckolib-forge-1.20.1"
+                },
+                "version": {"range": "[4.2,)", "artifactVersion": "4.2.2"},
+                "path": "META-INF/jarjar/geckolib.jar",
+                "isObfuscated": false
+            }]
+        });
+        let geckolib = jar(&[("META-INF/mods.toml", mods_toml("geckolib"))]);
+        let unlisted = jar(&[("META-INF/mods.toml", mods_toml("unlisted"))]);
+        write_mod(
+            dir.path(),
+            "forge.jar",
+            jar(&[
+                ("META-INF/mods.toml", mods_toml("mowziesmobs")),
+                (JARJAR_METADATA, metadata.to_string().into_bytes()),
+                ("META-INF/jarjar/geckolib.jar", geckolib),
+                // Not listed by the metadata, so not loaded by Forge either
+                ("META-INF/jarjar/unlisted.jar", unlisted),
+            ]),
+        );
+
+        // Without metadata every jar of the folder is read
+        let flywheel = jar(&[("META-INF/neoforge.mods.toml", mods_toml("flywheel"))]);
+        let library = jar(&[("org/library/Library.class", vec![])]);
+        write_mod(
+            dir.path(),
+            "neoforge.jar",
+            jar(&[
+                ("META-INF/neoforge.mods.toml", mods_toml("create")),
+                ("META-INF/jarjar/flywheel.jar", flywheel),
+                ("META-INF/jarjar/library.jar", library),
+            ]),
+        );
+
+        let mods = list(dir.path()).unwrap();
+
+        assert_eq!(mods[0].provides, ["geckolib"]);
+        assert_eq!(mods[1].provides, ["flywheel"]);
+    }
+
+    #[test]
+    fn strips_a_single_disabled_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let sodium = fabric_jar("sodium", &[], vec![]);
+        write_mod(dir.path(), "sodium.jar", sodium.clone());
+        write_mod(dir.path(), "lithium.jar.disabled", sodium.clone());
+        write_mod(dir.path(), "iris.jar.disabled.disabled", sodium);
+
+        let mods = list(dir.path()).unwrap();
+        let listed = mods
+            .iter()
+            .map(|it| (it.file_name.as_str(), it.enabled))
+            .collect::<Vec<_>>();
+
+        assert_eq!(listed, [("lithium.jar.disabled", false), ("sodium.jar", true)]);
+        assert_eq!(set_enabled(dir.path(), "sodium.jar", false).unwrap(), "sodium.jar.disabled");
+        assert_eq!(set_enabled(dir.path(), "lithium.jar.disabled", true).unwrap(), "lithium.jar");
+        // Not listed, so not toggled either
+        assert!(matches!(
+            set_enabled(dir.path(), "iris.jar.disabled.disabled", true),
+            Err(ManagerError::UnknownMod(_))
+        ));
+    }
+
+    #[test]
+    fn only_toggles_jars() {
+        let dir = tempfile::tempdir().unwrap();
+        write_mod(dir.path(), "notes.txt", vec![]);
+        write_mod(dir.path(), "notes.txt.disabled", vec![]);
+
+        for file_name in ["notes.txt", "notes.txt.disabled"] {
+            assert!(matches!(
+                set_enabled(dir.path(), file_name, true),
+                Err(ManagerError::UnknownMod(_))
+            ));
+            assert!(matches!(
+                set_enabled(dir.path(), file_name, false),
+                Err(ManagerError::UnknownMod(_))
+            ));
+        }
+        assert!(mods_path(dir.path()).join("notes.txt").exists());
+        assert!(!mods_path(dir.path()).join("notes.txt.disabled.disabled").exists());
+    }
+}
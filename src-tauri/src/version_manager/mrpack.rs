@@ -0,0 +1,441 @@
+use crate::version_manager::archive;
+use crate::version_manager::errors::ManagerError;
+use crate::version_manager::instances::Loader;
+use crate::version_manager::version::VersionId;
+use futures::StreamExt;
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use zip::ZipArchive;
+
+const INDEX_FILE: &str = "modrinth.index.json";
+const OVERRIDES_FOLDER: &str = "overrides";
+/// Applied after the common overrides, so they win over them.
+const CLIENT_OVERRIDES_FOLDER: &str = "client-overrides";
+const SUPPORTED_FORMAT: u32 = 1;
+const CONCURRENT_DOWNLOADS: usize = 8;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvSupport {
+    Required,
+    Optional,
+    Unsupported,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct FileEnv {
+    pub client: EnvSupport,
+    pub server: EnvSupport,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PackFile {
+    /// Destination, relative to the instance directory.
+    pub path: String,
+    pub hashes: HashMap<String, String>,
+    /// Files without it are needed on both sides.
+    #[serde(default)]
+    pub env: Option<FileEnv>,
+    /// Mirrors of the same file, tried in order.
+    pub downloads: Vec<String>,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+}
+
+impl PackFile {
+    pub fn is_client_file(&self) -> bool {
+        self.env
+            .as_ref()
+            .map_or(true, |env| env.client != EnvSupport::Unsupported)
+    }
+
+    /// Destination inside `instance_path`, unless the path tries to escape it.
+    pub fn destination(&self, instance_path: &Path) -> Option<PathBuf> {
+        let path = Path::new(&self.path);
+
+        match archive::is_enclosed(path) {
+            true => Some(instance_path.join(path)),
+            false => None,
+        }
+    }
+
+    /// Sha512 is preferred, sha1 is the only one the format requires.
+    fn verify(&self, bytes: &[u8]) -> Result<(), ManagerError> {
+        let (expected, actual) = match (self.hashes.get("sha512"), self.hashes.get("sha1")) {
+            (Some(expected), _) => (expected, format!("{:x}", Sha512::digest(bytes))),
+            (None, Some(expected)) => (expected, format!("{:x}", Sha1::digest(bytes))),
+            (None, None) => {
+                return Err(ManagerError::UnsupportedArchive(format!(
+                    "{} has no hash",
+                    self.path
+                )))
+            }
+        };
+
+        match expected.eq_ignore_ascii_case(&actual) {
+            true => Ok(()),
+            false => Err(ManagerError::ChecksumMismatch(self.path.clone())),
+        }
+    }
+}
+
+/// Contents of `modrinth.index.json`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PackIndex {
+    pub format_version: u32,
+    pub game: String,
+    pub version_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub files: Vec<PackFile>,
+    /// Minecraft and loader versions, e.g. `minecraft` and `fabric-loader`.
+    pub dependencies: HashMap<String, String>,
+}
+
+impl PackIndex {
+    pub fn read(archive: &Path) -> Result<PackIndex, ManagerError> {
+        let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+        let index: PackIndex = serde_json::from_reader(zip.by_name(INDEX_FILE)?)?;
+
+        if index.format_version != SUPPORTED_FORMAT {
+            return Err(ManagerError::UnsupportedArchive(format!(
+                "mrpack format {}",
+                index.format_version
+            )));
+        }
+
+        if index.game != "minecraft" {
+            return Err(ManagerError::UnsupportedArchive(format!("game {}", index.game)));
+        }
+
+        Ok(index)
+    }
+
+    pub fn minecraft_version(&self) -> Result<VersionId, ManagerError> {
+        self.dependencies
+            .get("minecraft")
+            .map(|version| VersionId::new(version))
+            .ok_or_else(|| {
+                ManagerError::UnsupportedArchive("no minecraft dependency".to_string())
+            })
+    }
+
+    pub fn loader(&self) -> Loader {
+        let version = |key: &str| self.dependencies.get(key).cloned();
+
+        if let Some(version) = version("fabric-loader") {
+            Loader::Fabric { version }
+        } else if let Some(version) = version("quilt-loader") {
+            Loader::Quilt { version }
+        } else if let Some(version) = version("forge") {
+            Loader::Forge { version }
+        } else if let Some(version) = version("neoforge") {
+            Loader::NeoForge { version }
+        } else {
+            Loader::Vanilla
+        }
+    }
+
+    pub fn client_files(&self) -> impl Iterator<Item = &PackFile> {
+        self.files.iter().filter(|file| file.is_client_file())
+    }
+}
+
+/// Copies the overrides of the pack into the instance directory.
+pub fn apply_overrides(archive: &Path, instance_path: &Path) -> Result<(), ManagerError> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+
+    let common = archive::extract_folder(&mut zip, OVERRIDES_FOLDER, instance_path)?;
+    let client = archive::extract_folder(&mut zip, CLIENT_OVERRIDES_FOLDER, instance_path)?;
+
+    info!("Applied {} overrides and {} client overrides", common, client);
+    Ok(())
+}
+
+/// Downloads the client files of the pack into the instance directory.
+pub async fn download_files(
+    client: &reqwest::Client,
+    index: &PackIndex,
+    instance_path: &Path,
+) -> Result<(), ManagerError> {
+    let results = futures::stream::iter(index.client_files())
+        .map(|file| download_file(client, file, instance_path))
+        .buffer_unordered(CONCURRENT_DOWNLOADS)
+        .collect::<Vec<_>>()
+        .await;
+
+    results.into_iter().collect()
+}
+
+async fn download_file(
+    client: &reqwest::Client,
+    file: &PackFile,
+    instance_path: &Path,
+) -> Result<(), ManagerError> {
+    let destination = file.destination(instance_path).ok_or_else(|| {
+        ManagerError::UnsupportedArchive(format!("{} is outside of the instance", file.path))
+    })?;
+
+    let mut last_error = ManagerError::NotFound;
+
+    for url in &file.downloads {
+        let bytes = match fetch(client, url).await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                warn!("Could not download {} from {}: {}", file.path, url, err);
+                last_error = err;
+                continue;
+            }
+        };
+
+        if let Err(err) = file.verify(&bytes) {
+            warn!("Discarding {} from {}: {}", file.path, url, err);
+            last_error = err;
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&destination, &bytes)?;
+
+        return Ok(());
+    }
+
+    Err(last_error)
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, ManagerError> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    Ok(bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestServer;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const SODIUM: &[u8] = b"sodium";
+
+    /// Index of a Fabric pack as exported by the Modrinth app.
+    fn index() -> serde_json::Value {
+        serde_json::json!({
+            "formatVersion": 1,
+            "game": "minecraft",
+            "versionId": "1.0.0",
+            "name": "Fabulously Optimized",
+            "files": [
+                {
+                    "path": "mods/sodium.jar",
+                    "hashes": {
+                        "sha1": format!("{:x}", Sha1::digest(SODIUM)),
+                        "sha512": format!("{:x}", Sha512::digest(SODIUM)),
+                    },
+                    "env": {"client": "required", "server": "required"},
+                    "downloads": ["https://cdn.modrinth.com/data/AANobbMI/versions/sodium.jar"],
+                    "fileSize": 6
+                },
+                {
+                    "path": "mods/server-only.jar",
+                    "hashes": {"sha1": "0000"},
+                    "env": {"client": "unsupported", "server": "required"},
+                    "downloads": []
+                },
+                {
+                    "path": "mods/zoomify.jar",
+                    "hashes": {"sha1": "0000"},
+                    "env": {"client": "optional", "server": "unsupported"},
+                    "downloads": []
+                },
+                {
+                    "path": "config/both.json",
+                    "hashes": {"sha1": "0000"},
+                    "downloads": []
+                }
+            ],
+            "dependencies": {
+                "minecraft": "1.20.1",
+                "fabric-loader": "0.14.21"
+            }
+        })
+    }
+
+    fn write_pack(path: &Path, index: &serde_json::Value, entries: &[(&str, &str)]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+
+        zip.start_file(INDEX_FILE, FileOptions::default()).unwrap();
+        zip.write_all(index.to_string().as_bytes()).unwrap();
+
+        for (name, contents) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+
+        zip.finish().unwrap();
+    }
+
+    fn file(path: &str, hashes: &[(&str, String)], downloads: Vec<String>) -> PackFile {
+        PackFile {
+            path: path.to_string(),
+            hashes: hashes
+                .iter()
+                .map(|(algorithm, hash)| (algorithm.to_string(), hash.clone()))
+                .collect(),
+            env: None,
+            downloads,
+            file_size: None,
+        }
+    }
+
+    #[test]
+    fn reads_the_index_and_overrides_of_a_pack() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack = dir.path().join("pack.mrpack");
+        write_pack(
+            &pack,
+            &index(),
+            &[
+                ("overrides/options.txt", "fov:70"),
+                ("overrides/config/sodium.json", "{}"),
+                ("client-overrides/options.txt", "fov:90"),
+            ],
+        );
+
+        let index = PackIndex::read(&pack).unwrap();
+        let instance = dir.path().join("instance");
+        apply_overrides(&pack, &instance).unwrap();
+
+        assert_eq!(index.name, "Fabulously Optimized");
+        assert_eq!(index.minecraft_version().unwrap(), VersionId::new("1.20.1"));
+        assert_eq!(index.loader(), Loader::Fabric { version: "0.14.21".to_string() });
+        assert_eq!(fs::read_to_string(instance.join("options.txt")).unwrap(), "fov:90");
+        assert!(instance.join("config/sodium.json").exists());
+    }
+
+    #[test]
+    fn refuses_unknown_formats_and_games() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack = dir.path().join("pack.mrpack");
+
+        let changes = [
+            ("formatVersion", serde_json::json!(2)),
+            ("game", serde_json::json!("terraria")),
+        ];
+
+        for (key, value) in changes {
+            let mut index = index();
+            index[key] = value;
+            write_pack(&pack, &index, &[]);
+
+            assert!(matches!(
+                PackIndex::read(&pack),
+                Err(ManagerError::UnsupportedArchive(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn keeps_the_files_needed_by_the_client() {
+        let index: PackIndex = serde_json::from_value(index()).unwrap();
+        let paths = index.client_files().map(|file| file.path.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(paths, ["mods/sodium.jar", "mods/zoomify.jar", "config/both.json"]);
+    }
+
+    #[test]
+    fn refuses_destinations_outside_of_the_instance() {
+        let instance = Path::new("instances").join("modded");
+        let destination = |path: &str| file(path, &[], vec![]).destination(&instance);
+
+        assert_eq!(destination("mods/sodium.jar"), Some(instance.join("mods/sodium.jar")));
+        assert_eq!(destination("../escaped.jar"), None);
+        assert_eq!(destination("mods/../../escaped.jar"), None);
+        assert_eq!(destination("/etc/escaped.jar"), None);
+        assert_eq!(destination(""), None);
+    }
+
+    #[test]
+    fn verifies_the_strongest_hash() {
+        let sha1 = format!("{:x}", Sha1::digest(SODIUM));
+        let sha512 = format!("{:x}", Sha512::digest(SODIUM));
+        let wrong_sha512 = format!("{:x}", Sha512::digest(b"lithium"));
+
+        assert!(file("a", &[("sha1", sha1.clone())], vec![]).verify(SODIUM).is_ok());
+        assert!(file("a", &[("sha512", sha512.to_uppercase())], vec![]).verify(SODIUM).is_ok());
+        assert!(matches!(
+            file("a", &[("sha1", "0".repeat(40))], vec![]).verify(SODIUM),
+            Err(ManagerError::ChecksumMismatch(_))
+        ));
+        // A matching sha1 doesn't make up for a wrong sha512
+        assert!(matches!(
+            file("a", &[("sha1", sha1), ("sha512", wrong_sha512)], vec![]).verify(SODIUM),
+            Err(ManagerError::ChecksumMismatch(_))
+        ));
+        assert!(matches!(
+            file("a", &[], vec![]).verify(SODIUM),
+            Err(ManagerError::UnsupportedArchive(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_next_mirror() {
+        let server = TestServer::start(|req| match req.path.as_str() {
+            "/corrupted/sodium.jar" => (200, b"corrupted".to_vec()),
+            "/cdn/sodium.jar" => (200, SODIUM.to_vec()),
+            _ => (404, vec![]),
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let mods = file(
+            "mods/sodium.jar",
+            &[("sha512", format!("{:x}", Sha512::digest(SODIUM)))],
+            vec![
+                server.url("/missing/sodium.jar"),
+                server.url("/corrupted/sodium.jar"),
+                server.url("/cdn/sodium.jar"),
+            ],
+        );
+
+        download_file(&reqwest::Client::new(), &mods, dir.path())
+            .await
+            .unwrap();
+
+        let paths = server.requests().into_iter().map(|req| req.path).collect::<Vec<_>>();
+        assert_eq!(paths, ["/missing/sodium.jar", "/corrupted/sodium.jar", "/cdn/sodium.jar"]);
+        assert_eq!(fs::read(dir.path().join("mods/sodium.jar")).unwrap(), SODIUM);
+    }
+
+    #[tokio::test]
+    async fn fails_when_every_mirror_fails() {
+        let server = TestServer::start(|_| (200, b"corrupted".to_vec())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let mods = file(
+            "mods/sodium.jar",
+            &[("sha1", format!("{:x}", Sha1::digest(SODIUM)))],
+            vec![server.url("/a/sodium.jar"), server.url("/b/sodium.jar")],
+        );
+
+        let result = download_file(&reqwest::Client::new(), &mods, dir.path()).await;
+
+        assert!(matches!(result, Err(ManagerError::ChecksumMismatch(_))));
+        assert!(!dir.path().join("mods/sodium.jar").exists());
+    }
+}
@@ -1,7 +1,7 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::path::{PathBuf};
 use crate::version_manager::asset::VersionManifest;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub struct VersionConstruct {
     pub id: VersionId,
@@ -10,19 +10,71 @@ pub struct VersionConstruct {
     pub asset_path: PathBuf,
     pub libraries_path: PathBuf,
     pub natives_temp_path: PathBuf,
+    /// Where the game looks for its assets, `${game_assets}` in legacy arguments.
+    pub game_assets: PathBuf,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
-pub enum VersionId {
-    V1_19_2,
-    V1_19_3,
-}
+/// Id of a Minecraft version as listed in the Mojang version manifest, e.g. `1.19.3`.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct VersionId(String);
+
+impl VersionId {
+    pub fn new(id: &str) -> VersionId {
+        VersionId(id.to_string())
+    }
+
+    /// Converts the ids written by older versions of the launcher, which stored
+    /// the name of an enum variant like `V1_19_3` instead of the version itself.
+    fn from_legacy(id: &str) -> Option<VersionId> {
+        let version = id.strip_prefix('V')?;
+
+        let is_legacy = !version.is_empty()
+            && version.chars().all(|c| c.is_ascii_digit() || c == '_');
 
-impl ToString for VersionId {
-    fn to_string(&self) -> String {
-        match self {
-            VersionId::V1_19_2 => "1.19.2".to_string(),
-            VersionId::V1_19_3 => "1.19.3".to_string(),
+        match is_legacy {
+            true => Some(VersionId(version.replace('_', "."))),
+            false => None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for VersionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let id = String::deserialize(deserializer)?;
+
+        Ok(VersionId::from_legacy(&id).unwrap_or(VersionId(id)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionType {
+    Release,
+    Snapshot,
+    OldBeta,
+    OldAlpha,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Entry of the version list shown to the user.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionSummary {
+    pub id: VersionId,
+    pub r#type: VersionType,
+    pub release_time: String,
+}
@@ -1,6 +1,8 @@
 export type Account = {
     username: string;
     uuid: string;
+    needsLogin: boolean;
+    offline: boolean;
 };
 
 export type AccountStore = {
@@ -8,8 +10,8 @@ export type AccountStore = {
     electedAccount?: Account;
 
     addAccount: (account: Account) => void;
-    removeAccount: (index: number) => void;
+    removeAccount: (uuid: string) => void;
     setAccounts: (accounts: Array<Account>) => void;
     fetchAccounts: () => Promise<void>;
-    pickAccount: (index: number) => Promise<void>;
+    pickAccount: (uuid: string) => Promise<void>;
 };
@@ -1,5 +1,6 @@
 import create from 'zustand';
 import { invoke } from '@tauri-apps/api';
+import { listen } from '@tauri-apps/api/event';
 import { Account, AccountStore } from './account';
 import { useEffect } from 'react';
 import { Game, GameStore } from './game';
@@ -46,17 +47,17 @@ export const useAccounts = create<AccountStore>(set => ({
             electedAccount,
         })),
 
-    removeAccount: (index: number) => {
+    removeAccount: (uuid: string) => {
         invoke('remove_account', {
-            account: index,
-        });
+            account: uuid,
+        }).catch(console.error);
 
         set(({ electedAccount, accounts }) => {
             const removeIsElected =
-                electedAccount && accounts[index].uuid === electedAccount.uuid;
+                electedAccount && uuid === electedAccount.uuid;
 
             return {
-                accounts: accounts.filter((_, i) => i !== index),
+                accounts: accounts.filter(it => it.uuid !== uuid),
                 electedAccount: removeIsElected ? undefined : electedAccount,
             };
         });
@@ -78,6 +79,8 @@ export const useAccounts = create<AccountStore>(set => ({
                 (it: any) => ({
                     uuid: it.profile.id,
                     username: it.profile.name,
+                    needsLogin: it.needs_login,
+                    offline: it.kind === 'Offline',
                 } as Account),
             )[0];
 
@@ -85,6 +88,8 @@ export const useAccounts = create<AccountStore>(set => ({
             (it: any) => ({
                 uuid: it.profile.id,
                 username: it.profile.name,
+                needsLogin: it.needs_login,
+                offline: it.kind === 'Offline',
             } as Account),
         );
 
@@ -94,14 +99,14 @@ export const useAccounts = create<AccountStore>(set => ({
         }));
     },
 
-    pickAccount: async (index: number) => {
+    pickAccount: async (uuid: string) => {
         await invoke('elect_account', {
-            account: index,
+            account: uuid,
         });
 
         set(state => ({
             ...state,
-            electedAccount: state.accounts[index],
+            electedAccount: state.accounts.find(it => it.uuid === uuid),
         }));
     },
 }));
@@ -112,5 +117,13 @@ export function useInitializeStores() {
     useEffect(() => {
         accounts.fetchAccounts().then();
         games.fetchGames().then();
+
+        const unlisten = listen('accounts:state', () => {
+            accounts.fetchAccounts().then();
+        });
+
+        return () => {
+            unlisten.then(fn => fn());
+        };
     }, []);
 }
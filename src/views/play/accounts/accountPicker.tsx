@@ -77,12 +77,12 @@ export function AccountPicker() {
                 </span>
             </div>
             <div>
-                {accounts.map((it, index) => (
+                {accounts.map(it => (
                     <Account
-                        key={index}
+                        key={it.uuid}
                         name={it.username}
-                        onRemove={() => removeAccount(index)}
-                        onPick={() => pickAccount(index)}
+                        onRemove={() => removeAccount(it.uuid)}
+                        onPick={() => pickAccount(it.uuid)}
                     />
                 ))}
             </div>
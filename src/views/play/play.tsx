@@ -12,7 +12,7 @@ interface AuthStateEvent {
 }
 
 export function Play() {
-    const { electedAccount, removeAccount, accounts, fetchAccounts } = useAccounts();
+    const { electedAccount, removeAccount, fetchAccounts } = useAccounts();
     const { selectedGame } = useGames();
 
     const {
@@ -76,8 +76,7 @@ export function Play() {
                         <button
                             className='text-lg'
                             onClick={() => {
-                                const index = accounts.findIndex(account => account.uuid === electedAccount?.uuid);
-                                removeAccount(index);
+                                if (electedAccount) removeAccount(electedAccount.uuid);
                                 setMenuIsActive(false);
                                 setNotLoggedInMsg('Not logged in!');
                             }}